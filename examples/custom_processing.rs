@@ -52,7 +52,11 @@ impl ProcessingStrategy for CsvValidationStrategy {
         let lines: Vec<&str> = text.lines().collect();
         
         if lines.is_empty() {
-            return Err(AppError::Processing("CSV file is empty".to_string()));
+            return Err(AppError::Rejected {
+                strategy: self.name().to_string(),
+                file: file_name.to_string(),
+                reason: "CSV file is empty".to_string(),
+            });
         }
         
         let header = lines[0];
@@ -131,17 +135,17 @@ pub fn demonstrate_custom_strategies() {
     let config = ProcessingConfig::default();
     
     // Create processors with different strategies
-    let image_processor = FileProcessor::with_strategy(
+    let _image_processor = FileProcessor::with_strategy(
         config.clone(),
         Arc::new(ImageMetadataStrategy)
     );
     
-    let csv_processor = FileProcessor::with_strategy(
+    let _csv_processor = FileProcessor::with_strategy(
         config.clone(),
         Arc::new(CsvValidationStrategy)
     );
     
-    let encryption_processor = FileProcessor::with_strategy(
+    let _encryption_processor = FileProcessor::with_strategy(
         config,
         Arc::new(EncryptionStrategy::new("my-secret-key".to_string()))
     );
@@ -150,4 +154,8 @@ pub fn demonstrate_custom_strategies() {
     println!("- Image metadata processor");
     println!("- CSV validation processor");
     println!("- Encryption processor");
-}
\ No newline at end of file
+}
+
+fn main() {
+    demonstrate_custom_strategies();
+}
@@ -0,0 +1,54 @@
+;; Example processing plugin: upper-cases ASCII letters.
+;;
+;; Convert it to a module in the plugin directory and select it by name:
+;;
+;;   wasm-tools parse examples/plugins/uppercase.wat -o plugins/uppercase.wasm
+;;   cargo run -- --strategy uppercase
+;;
+;; Every file gets a fresh instance, so the bump allocator never frees anything.
+(module
+  (import "cfp" "log" (func $log (param i32 i32)))
+  (import "cfp" "fail" (func $fail (param i32 i32)))
+
+  (memory (export "memory") 1)
+  (global $heap (mut i32) (i32.const 1024))
+  (data (i32.const 0) "empty file")
+
+  ;; Reserve `len` bytes for the host to copy the file name and content into
+  (func (export "alloc") (param $len i32) (result i32)
+    (local $ptr i32)
+    (local $end i32)
+    (local.set $ptr (global.get $heap))
+    (local.set $end (i32.add (local.get $ptr) (local.get $len)))
+    (if (i32.gt_u (local.get $end) (i32.mul (memory.size) (i32.const 65536)))
+      (then
+        (if (i32.eq
+              (memory.grow (i32.div_u
+                (i32.add (i32.sub (local.get $end) (i32.mul (memory.size) (i32.const 65536))) (i32.const 65535))
+                (i32.const 65536)))
+              (i32.const -1))
+          (then unreachable))))
+    (global.set $heap (local.get $end))
+    (local.get $ptr))
+
+  ;; Upper-case the content in place and return it as (address << 32) | length
+  (func (export "process") (param $name i32) (param $name_len i32) (param $data i32) (param $len i32) (result i64)
+    (local $i i32)
+    (local $byte i32)
+    (if (i32.eqz (local.get $len))
+      (then (call $fail (i32.const 0) (i32.const 10))))
+    (call $log (local.get $name) (local.get $name_len))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $len)))
+        (local.set $byte (i32.load8_u (i32.add (local.get $data) (local.get $i))))
+        (if (i32.and
+              (i32.ge_u (local.get $byte) (i32.const 97))
+              (i32.le_u (local.get $byte) (i32.const 122)))
+          (then (i32.store8 (i32.add (local.get $data) (local.get $i)) (i32.sub (local.get $byte) (i32.const 32)))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (i64.or
+      (i64.shl (i64.extend_i32_u (local.get $data)) (i64.const 32))
+      (i64.extend_i32_u (local.get $len))))
+)
@@ -0,0 +1,49 @@
+//! Local stand-in for a webhook endpoint
+//!
+//! Prints every webhook it receives and checks its signature, for trying out
+//! `[[webhook.targets]]` settings without a real downstream service.
+//!
+//! ```bash
+//! WEBHOOK_SECRET=s3cret cargo run --example webhook_receiver
+//! ```
+//!
+//! `WEBHOOK_LISTEN` changes the address (default `127.0.0.1:9899`) and
+//! `WEBHOOK_STATUS` the status code answered, e.g. `503` to watch the retries.
+
+use concurrent_file_processor::http::{serve, Response};
+use concurrent_file_processor::webhook::{verify_signature, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER};
+use tokio::net::TcpListener;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let listen = std::env::var("WEBHOOK_LISTEN").unwrap_or_else(|_| "127.0.0.1:9899".to_string());
+    let secret = std::env::var("WEBHOOK_SECRET").unwrap_or_default();
+    let status: u16 = std::env::var("WEBHOOK_STATUS").ok().and_then(|status| status.parse().ok()).unwrap_or(200);
+
+    let listener = TcpListener::bind(&listen).await?;
+    println!("Receiving webhooks at http://{}/ (answering {})", listener.local_addr()?, status);
+
+    serve(listener, move |request| {
+        let secret = secret.clone();
+        async move {
+            let header = |name: &str| request.header(&name.to_ascii_lowercase()).unwrap_or("-").to_string();
+            let signature = match request.header(&SIGNATURE_HEADER.to_ascii_lowercase()) {
+                None => "unsigned",
+                Some(_) if secret.is_empty() => "not checked (WEBHOOK_SECRET is not set)",
+                Some(signature) if verify_signature(&secret, &request.body, signature) => "valid",
+                Some(_) => "INVALID",
+            };
+            println!(
+                "{} {} | event: {} | delivery: {} | signature: {}\n{}\n",
+                request.method,
+                request.path,
+                header(EVENT_HEADER),
+                header(DELIVERY_HEADER),
+                signature,
+                String::from_utf8_lossy(&request.body)
+            );
+            Response::text(status, "")
+        }
+    }).await;
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use crate::config::HttpConfig;
+use crate::http::{HttpState, Request, Response};
+use crate::job::JobId;
+use crate::logging::{log_info, log_warning};
+use crate::watcher::enqueue_existing_files;
+
+use serde_json::json;
+
+/// Number of queued files listed by `GET /admin/jobs` unless `limit` is given
+const DEFAULT_QUEUED_LIMIT: usize = 100;
+
+/// Configuration fields replaced by a placeholder in `GET /admin/config`;
+/// lists on the way, such as `webhook.targets`, are searched item by item
+const SECRET_FIELDS: &[&str] = &["http.admin_token", "webhook.targets.secret", "processing.command.env"];
+
+/// Answer a request to the admin API
+pub async fn handle(config: &HttpConfig, state: &HttpState, request: &Request) -> Response {
+    if config.admin_token.is_empty() {
+        log_warning("Rejected admin request, no admin token is configured", &format!("{} {}", request.method, request.path));
+        return Response::text(403, "The admin API requires http.admin_token to be set\n");
+    }
+    if !is_authorized(config, request) {
+        log_warning("Rejected unauthorized admin request", &format!("{} {}", request.method, request.path));
+        return Response::text(401, "Missing or invalid admin token\n");
+    }
+
+    let method = request.method.as_str();
+    if method == "POST" {
+        log_info("Admin request", &format!("{} {}", request.method, request.path));
+    }
+
+    if method == "POST" && let Some(job_id) = retry_target(&request.path) {
+        return retry(state, job_id);
+    }
+
+    match (method, request.path.as_str()) {
+        ("GET", "/admin/status") => status(state),
+        ("GET", "/admin/jobs") => jobs(state, request),
+        ("GET", "/admin/config") => effective_config(state),
+        ("POST", "/admin/pause") => {
+            state.queue.pause();
+            status(state)
+        }
+        ("POST", "/admin/resume") => {
+            state.queue.resume();
+            status(state)
+        }
+        ("POST", "/admin/rescan") => rescan(state).await,
+        ("POST", "/admin/shutdown") => shutdown(state),
+        (_, path) if is_admin_route(path) => Response::text(405, "Method not allowed\n"),
+        _ => Response::not_found(),
+    }
+}
+
+/// Whether the request carries the configured admin token. Without a token
+/// nothing is authorized, since a web page could otherwise send requests to a
+/// service on the same machine.
+fn is_authorized(config: &HttpConfig, request: &Request) -> bool {
+    if config.admin_token.is_empty() {
+        return false;
+    }
+    let Some(token) = request.header("authorization").and_then(|value| value.strip_prefix("Bearer ")) else {
+        return false;
+    };
+    // Compare every byte so the time taken does not reveal how much of the token matched
+    token.len() == config.admin_token.len()
+        && token.bytes().zip(config.admin_token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Job ID in `/admin/jobs/<id>/retry`
+fn retry_target(path: &str) -> Option<&str> {
+    path.strip_prefix("/admin/jobs/")?.strip_suffix("/retry")
+}
+
+fn is_admin_route(path: &str) -> bool {
+    matches!(
+        path,
+        "/admin/status" | "/admin/jobs" | "/admin/config" | "/admin/pause" | "/admin/resume" | "/admin/rescan" | "/admin/shutdown"
+    ) || retry_target(path).is_some()
+}
+
+fn status(state: &HttpState) -> Response {
+    let processor = state.processor.current();
+    Response::json(200, &json!({
+        "paused": state.queue.is_paused(),
+        "shutting_down": state.queue.is_closed(),
+        "in_flight": processor.in_flight_count(),
+        "recently_failed": processor.jobs().failed().len(),
+        "queue": state.queue.stats(),
+    }))
+}
+
+fn jobs(state: &HttpState, request: &Request) -> Response {
+    let limit = match request.query_param("limit").map(str::parse::<usize>) {
+        None => DEFAULT_QUEUED_LIMIT,
+        Some(Ok(limit)) => limit,
+        Some(Err(_)) => return Response::text(400, "limit must be a non-negative number\n"),
+    };
+    let processor = state.processor.current();
+    Response::json(200, &json!({
+        "in_flight": processor.jobs().running(),
+        "queued": state.queue.snapshot(limit),
+        "failed": processor.jobs().failed(),
+    }))
+}
+
+/// The configuration currently in effect, with secrets replaced
+fn effective_config(state: &HttpState) -> Response {
+    let mut config = match serde_json::to_value(&*state.config.borrow()) {
+        Ok(config) => config,
+        Err(e) => return Response::text(500, format!("Failed to serialize configuration: {}\n", e)),
+    };
+    for field in SECRET_FIELDS {
+        redact(&mut config, &field.split('.').collect::<Vec<_>>());
+    }
+    Response::json(200, &config)
+}
+
+/// Replace the non-empty string at `path` below `value`
+fn redact(value: &mut serde_json::Value, path: &[&str]) {
+    match (value, path) {
+        (serde_json::Value::Array(items), _) => {
+            for item in items {
+                redact(item, path);
+            }
+        }
+        (value, []) => {
+            if value.as_str().is_some_and(|secret| !secret.is_empty()) {
+                *value = json!("<redacted>");
+            }
+        }
+        (value, [key, rest @ ..]) => {
+            if let Some(child) = value.get_mut(*key) {
+                redact(child, rest);
+            }
+        }
+    }
+}
+
+async fn rescan(state: &HttpState) -> Response {
+    match enqueue_existing_files(&state.input_dir, &state.queue, state.recursive).await {
+        Ok(queued) => Response::json(200, &json!({ "queued": queued })),
+        Err(e) => Response::text(500, format!("Rescan failed: {:#}\n", e)),
+    }
+}
+
+/// Queue the file of a failed job again
+fn retry(state: &HttpState, job_id: &str) -> Response {
+    let Ok(job_id) = job_id.parse::<JobId>() else {
+        return Response::text(400, format!("'{}' is not a job ID\n", job_id));
+    };
+    let processor = state.processor.current();
+    let Some(job) = processor.jobs().find_failed(job_id) else {
+        return Response::text(404, format!("No recently failed job {}\n", job_id));
+    };
+    if !job.file.exists() {
+        processor.jobs().remove_failed(job_id);
+        return Response::text(404, format!("{} no longer exists\n", job.file.display()));
+    }
+    // Forget the failure first, so the file is not skipped as unchanged when it is claimed
+    processor.jobs().remove_failed(job_id);
+    if !state.queue.push(job.file.clone()) {
+        let file = job.file.clone();
+        processor.jobs().restore_failed(job);
+        return Response::text(409, format!("{} is already queued or the queue is full\n", file.display()));
+    }
+    log_info("Failed file queued again", &format!("{} (job {})", job.file.display(), job_id));
+    Response::json(202, &json!({ "queued": job.file }))
+}
+
+/// Stop taking new jobs; the service exits once running jobs have finished
+fn shutdown(state: &HttpState) -> Response {
+    log_info("Graceful shutdown requested", "finishing running jobs");
+    state.queue.close();
+    Response::json(202, &json!({ "shutting_down": true, "in_flight": state.processor.current().in_flight_count() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request_with_auth(authorization: Option<&str>) -> Request {
+        Request {
+            method: "POST".to_string(),
+            path: "/admin/pause".to_string(),
+            query: String::new(),
+            headers: authorization.map(|value| ("authorization".to_string(), value.to_string())).into_iter().collect(),
+            body: Vec::new(),
+        }
+    }
+
+    fn config_with_token(token: &str) -> HttpConfig {
+        HttpConfig { admin: true, admin_token: token.to_string(), ..HttpConfig::default() }
+    }
+
+    #[test]
+    fn an_empty_token_authorizes_nothing() {
+        let config = config_with_token("");
+        assert!(!is_authorized(&config, &request_with_auth(None)));
+        assert!(!is_authorized(&config, &request_with_auth(Some("Bearer "))));
+    }
+
+    #[test]
+    fn only_the_configured_bearer_token_is_accepted() {
+        let config = config_with_token("s3cret");
+        assert!(is_authorized(&config, &request_with_auth(Some("Bearer s3cret"))));
+        assert!(!is_authorized(&config, &request_with_auth(Some("Bearer s3cre"))));
+        assert!(!is_authorized(&config, &request_with_auth(Some("Bearer s3cret2"))));
+        assert!(!is_authorized(&config, &request_with_auth(Some("s3cret"))));
+        assert!(!is_authorized(&config, &request_with_auth(None)));
+    }
+
+    #[test]
+    fn redact_replaces_non_empty_secrets_in_nested_lists() {
+        let mut config = json!({
+            "http": { "admin_token": "s3cret" },
+            "webhook": { "targets": [
+                { "url": "http://a", "secret": "key" },
+                { "url": "http://b", "secret": "" },
+            ] },
+            "processing": { "command": { "env": ["API_KEY=abc", "MODE=fast"] } },
+        });
+        redact(&mut config, &["http", "admin_token"]);
+        redact(&mut config, &["webhook", "targets", "secret"]);
+        redact(&mut config, &["processing", "command", "env"]);
+        redact(&mut config, &["missing", "field"]);
+
+        assert_eq!(config["http"]["admin_token"], "<redacted>");
+        assert_eq!(config["webhook"]["targets"][0]["secret"], "<redacted>");
+        assert_eq!(config["webhook"]["targets"][0]["url"], "http://a");
+        assert_eq!(config["webhook"]["targets"][1]["secret"], "", "an empty secret shows that none is set");
+        assert_eq!(config["processing"]["command"]["env"], json!(["<redacted>", "<redacted>"]));
+    }
+
+    #[test]
+    fn retry_routes_carry_the_job_id() {
+        assert_eq!(retry_target("/admin/jobs/abc/retry"), Some("abc"));
+        assert_eq!(retry_target("/admin/jobs/abc"), None);
+        assert!(is_admin_route("/admin/jobs/abc/retry"));
+        assert!(!is_admin_route("/admin/unknown"));
+    }
+}
@@ -0,0 +1,109 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+/// Watches an input directory and processes new files concurrently
+#[derive(Debug, Parser)]
+#[command(name = "concurrent_file_processor", version, about)]
+pub struct Cli {
+    /// Path to the configuration file (default: first of ./config.toml,
+    /// $XDG_CONFIG_HOME/concurrent_file_processor/config.toml, /etc/concurrent_file_processor/config.toml)
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Treat unknown configuration fields and environment variables as errors
+    #[arg(long, global = true)]
+    pub strict_config: bool,
+
+    /// Override the input directory
+    #[arg(long, global = true, value_name = "DIR")]
+    pub input: Option<PathBuf>,
+
+    /// Override the output directory
+    #[arg(long, global = true, value_name = "DIR")]
+    pub output: Option<PathBuf>,
+
+    /// Override the processing strategy
+    #[arg(long, global = true, value_name = "NAME")]
+    pub strategy: Option<String>,
+
+    /// Show which files would be processed and how, without touching any file
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Output format of the dry-run plan
+    #[arg(long, global = true, value_enum, default_value_t = PlanFormat::Table)]
+    pub format: PlanFormat,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Output format of the dry-run plan
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum PlanFormat {
+    Table,
+    Json,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Watch the input directory and process new files (default)
+    Run,
+    /// Process the files currently in the input directory and exit
+    Once,
+    /// Process the given files and exit
+    Process {
+        /// Files to process
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Ask the running service whether it is ready (or alive, with --live);
+    /// exits with a failure status if it is not or cannot be reached
+    Health {
+        /// Check liveness instead of readiness
+        #[arg(long)]
+        live: bool,
+    },
+    /// Manage the configuration file
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum ConfigCommand {
+    /// Write a commented default configuration file (to --config or ./config.toml)
+    Init {
+        /// Overwrite an existing file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check that the configuration can be loaded
+    Validate,
+    /// Print the configuration file search locations and the file in use
+    Path,
+    /// Print the effective configuration
+    Show {
+        /// Show where each value came from (default, file, env or command line)
+        #[arg(long)]
+        sources: bool,
+    },
+}
+
+impl Cli {
+    /// Configuration fields overridden on the command line, as dotted paths with raw values
+    pub fn overrides(&self) -> Vec<(String, String)> {
+        let mut overrides = Vec::new();
+        if let Some(input) = &self.input {
+            overrides.push(("directories.input_dir".to_string(), input.to_string_lossy().into_owned()));
+        }
+        if let Some(output) = &self.output {
+            overrides.push(("directories.output_dir".to_string(), output.to_string_lossy().into_owned()));
+        }
+        if let Some(strategy) = &self.strategy {
+            overrides.push(("processing.strategy".to_string(), strategy.clone()));
+        }
+        overrides
+    }
+}
@@ -0,0 +1,532 @@
+use crate::config::CommandConfig;
+use crate::error::{AppError, BoxError};
+use crate::logging::{log_debug, log_warning};
+use crate::processor::ProcessingStrategy;
+
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Replaced with the path of a temporary copy of the input file
+pub const INPUT_PLACEHOLDER: &str = "{input}";
+/// Replaced with the path the command writes its output to
+pub const OUTPUT_PLACEHOLDER: &str = "{output}";
+/// Replaced with the name of the input file
+pub const NAME_PLACEHOLDER: &str = "{name}";
+/// Environment variables carrying the same values, safe to use from shell scripts
+pub const INPUT_ENV: &str = "CFP_INPUT";
+pub const OUTPUT_ENV: &str = "CFP_OUTPUT";
+pub const NAME_ENV: &str = "CFP_NAME";
+/// Programs whose `-c` argument is a script that would interpret spliced-in file names
+const SHELLS: &[&str] = &["sh", "bash", "dash", "zsh", "ksh", "ash"];
+
+/// How often a running command is checked for having exited
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Tail of stderr kept for error messages and logs
+const STDERR_LIMIT: usize = 4096;
+
+/// Processing strategy that runs an external command for every file.
+///
+/// The file content is piped to the command's stdin and its stdout becomes the
+/// output, unless the arguments use `{input}` or `{output}`: then the content is
+/// written to a temporary file, or the output read from one, instead.
+pub struct CommandStrategy {
+    config: CommandConfig,
+    env: Vec<(String, String)>,
+}
+
+impl CommandStrategy {
+    pub fn new(config: &CommandConfig) -> Result<Self, AppError> {
+        if config.argv.first().is_none_or(|program| program.is_empty()) {
+            return Err(AppError::config("processing.command.argv must name the program to run"));
+        }
+        let env = config.env.iter()
+            .map(|entry| parse_env_entry(entry)
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .ok_or_else(|| AppError::config(format!("Invalid command environment entry '{}', expected KEY=VALUE", entry))))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { config: config.clone(), env })
+    }
+
+    fn uses(&self, placeholder: &str) -> bool {
+        self.config.argv.iter().any(|arg| arg.contains(placeholder))
+    }
+
+    fn uses_input_file(&self) -> bool {
+        self.config.input_file || self.uses(INPUT_PLACEHOLDER)
+    }
+
+    fn uses_output_file(&self) -> bool {
+        self.config.output_file || self.uses(OUTPUT_PLACEHOLDER)
+    }
+
+    fn program(&self) -> &str {
+        &self.config.argv[0]
+    }
+
+    fn build_command(&self, file_name: &str, input: Option<&Path>, output: Option<&Path>) -> Command {
+        let expand = |arg: &String| {
+            let mut arg = arg.replace(NAME_PLACEHOLDER, file_name);
+            if let Some(input) = input {
+                arg = arg.replace(INPUT_PLACEHOLDER, &input.to_string_lossy());
+            }
+            if let Some(output) = output {
+                arg = arg.replace(OUTPUT_PLACEHOLDER, &output.to_string_lossy());
+            }
+            arg
+        };
+
+        let mut command = Command::new(self.program());
+        command.args(self.config.argv[1..].iter().map(expand))
+            .stdin(if input.is_some() { Stdio::null() } else { Stdio::piped() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if self.config.clear_env {
+            command.env_clear();
+        }
+        command.envs(self.env.iter().map(|(key, value)| (key, value)));
+        command.env(NAME_ENV, file_name);
+        if let Some(input) = input {
+            command.env(INPUT_ENV, input);
+        }
+        if let Some(output) = output {
+            command.env(OUTPUT_ENV, output);
+        }
+        if !self.config.working_dir.is_empty() {
+            command.current_dir(&self.config.working_dir);
+        }
+        #[cfg(unix)]
+        apply_limits(&mut command, &self.config);
+        command
+    }
+}
+
+impl ProcessingStrategy for CommandStrategy {
+    fn name(&self) -> &str {
+        "command"
+    }
+
+    fn process_content(&self, file_name: &str, content: &[u8]) -> Result<String, AppError> {
+        let failed = |message: String, source: Option<BoxError>| AppError::Strategy {
+            strategy: self.name().to_string(),
+            file: file_name.to_string(),
+            message: format!("command '{}' {}", self.program(), message),
+            source,
+        };
+        let limit_exceeded = |limit: &str| AppError::LimitExceeded {
+            strategy: self.name().to_string(),
+            file: file_name.to_string(),
+            limit: format!("processing.command.{}", limit),
+        };
+
+        let scratch = if self.uses_input_file() || self.uses_output_file() {
+            Some(ScratchDir::create().map_err(|e| failed("could not get a temporary directory".to_string(), Some(e.into())))?)
+        } else {
+            None
+        };
+        let input = scratch.as_ref().filter(|_| self.uses_input_file()).map(|scratch| {
+            // Keep the original name so tools that look at the extension still work
+            let name = Path::new(file_name).file_name().map(PathBuf::from).unwrap_or_else(|| PathBuf::from("input"));
+            scratch.0.join(name)
+        });
+        let output = scratch.as_ref().filter(|_| self.uses_output_file()).map(|scratch| scratch.0.join("output"));
+        if let Some(input) = &input {
+            std::fs::write(input, content).map_err(|e| AppError::file("write", input, e))?;
+        }
+
+        let mut command = self.build_command(file_name, input.as_deref(), output.as_deref());
+        log_debug("Running command", &format!("{:?}", command));
+        let child = command.spawn().map_err(|e| failed("could not be started".to_string(), Some(e.into())))?;
+        let run = run_to_completion(
+            child,
+            input.is_none().then_some(content),
+            self.config.max_output_mb.saturating_mul(1024 * 1024) as usize,
+            Duration::from_secs(self.config.timeout_sec)
+        ).map_err(|e| failed("could not be run".to_string(), Some(e.into())))?;
+
+        let stderr = String::from_utf8_lossy(&run.stderr).trim().to_string();
+        let with_stderr = |message: String| if stderr.is_empty() { message } else { format!("{}; stderr: {}", message, stderr) };
+        let Some(status) = run.status else {
+            return Err(AppError::Timeout {
+                operation: format!("Command '{}' on {}", self.program(), file_name),
+                after: Duration::from_secs(self.config.timeout_sec),
+            });
+        };
+        match status.code() {
+            Some(code) if self.config.success_codes.contains(&code) => {}
+            Some(code) if self.config.retry_codes.contains(&code) => {
+                return Err(AppError::Unavailable {
+                    strategy: self.name().to_string(),
+                    file: file_name.to_string(),
+                    message: with_stderr(format!("command '{}' exited with code {}", self.program(), code)),
+                });
+            }
+            Some(code) if self.config.reject_codes.contains(&code) => {
+                return Err(AppError::Rejected {
+                    strategy: self.name().to_string(),
+                    file: file_name.to_string(),
+                    reason: with_stderr(format!("command '{}' exited with code {}", self.program(), code)),
+                });
+            }
+            Some(code) => return Err(failed(with_stderr(format!("exited with code {}", code)), None)),
+            #[cfg(unix)]
+            None if std::os::unix::process::ExitStatusExt::signal(&status) == Some(libc::SIGXCPU) => {
+                return Err(limit_exceeded("max_cpu_sec"));
+            }
+            None => return Err(failed(with_stderr(describe_termination(status)), None)),
+        }
+        if !stderr.is_empty() {
+            log_debug("Command wrote to stderr", &stderr);
+        }
+        if run.stdout_truncated && output.is_none() {
+            return Err(limit_exceeded("max_output_mb"));
+        }
+
+        let bytes = match &output {
+            Some(output) => {
+                let size = std::fs::metadata(output)
+                    .map_err(|e| failed("did not write its output file".to_string(), Some(e.into())))?
+                    .len();
+                if size > self.config.max_output_mb.saturating_mul(1024 * 1024) {
+                    return Err(limit_exceeded("max_output_mb"));
+                }
+                std::fs::read(output).map_err(|e| AppError::file("read", output, e))?
+            }
+            None => run.stdout,
+        };
+        String::from_utf8(bytes).map_err(|e| failed("wrote output that is not UTF-8 text".to_string(), Some(e.into())))
+    }
+}
+
+/// The script of a `sh -c` style command if it contains a placeholder. File names
+/// spliced into a script are interpreted by the shell, so scripts must use the
+/// `CFP_*` environment variables instead.
+pub(crate) fn templated_shell_script(argv: &[String]) -> Option<&str> {
+    let program = Path::new(argv.first()?).file_name()?.to_str()?;
+    if !SHELLS.contains(&program) {
+        return None;
+    }
+    let is_command_flag = |arg: &&String| arg.starts_with('-') && !arg.starts_with("--") && arg.contains('c');
+    let script = argv[1..].iter().skip_while(|arg| !is_command_flag(arg)).nth(1)?;
+    [INPUT_PLACEHOLDER, OUTPUT_PLACEHOLDER, NAME_PLACEHOLDER].iter()
+        .any(|placeholder| script.contains(placeholder))
+        .then_some(script.as_str())
+}
+
+/// Split an `env` entry into its key and value
+pub(crate) fn parse_env_entry(entry: &str) -> Option<(&str, &str)> {
+    entry.split_once('=').filter(|(key, _)| !key.is_empty())
+}
+
+/// Bytes read from a pipe and whether some were dropped
+type Captured = (Vec<u8>, bool);
+
+/// What a finished command produced
+struct CommandRun {
+    /// Exit status, or `None` if the command timed out and was killed
+    status: Option<ExitStatus>,
+    stdout: Vec<u8>,
+    stdout_truncated: bool,
+    /// The last `STDERR_LIMIT` bytes written to stderr
+    stderr: Vec<u8>,
+}
+
+/// Feed `stdin` to `child` and collect its output until it exits or `timeout` (if not zero) passes
+fn run_to_completion(mut child: Child, stdin: Option<&[u8]>, stdout_limit: usize, timeout: Duration) -> io::Result<CommandRun> {
+    let child_stdin = child.stdin.take();
+    let child_stdout = child.stdout.take();
+    let child_stderr = child.stderr.take();
+    let deadline = (!timeout.is_zero()).then(|| Instant::now() + timeout);
+
+    thread::scope(|scope| {
+        if let (Some(mut pipe), Some(content)) = (child_stdin, stdin) {
+            scope.spawn(move || {
+                // A command may exit without reading all of its input
+                if let Err(e) = pipe.write_all(content) && e.kind() != io::ErrorKind::BrokenPipe {
+                    log_warning("Failed to write command input", &e.to_string());
+                }
+            });
+        }
+        let stdout = child_stdout.map(|pipe| scope.spawn(move || read_limited(pipe, stdout_limit, false)));
+        let stderr = child_stderr.map(|pipe| scope.spawn(move || read_limited(pipe, STDERR_LIMIT, true)));
+
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                // Processes the command left running in the background would keep
+                // the output pipes open, and the readers below waiting for them
+                kill_process_group(&child);
+                break Some(status);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                kill(&mut child);
+                child.wait()?;
+                break None;
+            }
+            thread::sleep(POLL_INTERVAL);
+        };
+
+        let join = |reader: Option<thread::ScopedJoinHandle<'_, io::Result<Captured>>>| match reader {
+            Some(reader) => reader.join().unwrap_or_else(|_| Err(io::Error::other("output reader panicked"))),
+            None => Ok((Vec::new(), false)),
+        };
+        let (stdout, stdout_truncated) = join(stdout)?;
+        let (stderr, _) = join(stderr)?;
+        Ok(CommandRun { status, stdout, stdout_truncated, stderr })
+    })
+}
+
+/// Read `pipe` to the end, keeping at most `limit` bytes: the first ones, or the
+/// last ones with `keep_tail`. Returns whether anything was dropped.
+fn read_limited(mut pipe: impl Read, limit: usize, keep_tail: bool) -> io::Result<Captured> {
+    let mut data = Vec::new();
+    let mut truncated = false;
+    let mut buffer = [0u8; 8192];
+    loop {
+        let read = match pipe.read(&mut buffer) {
+            Ok(0) => return Ok((data, truncated)),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if keep_tail {
+            data.extend_from_slice(&buffer[..read]);
+            if data.len() > limit {
+                data.drain(..data.len() - limit);
+                truncated = true;
+            }
+        } else {
+            // Keep draining so the command is not blocked on a full pipe
+            let keep = read.min(limit - data.len());
+            data.extend_from_slice(&buffer[..keep]);
+            truncated |= keep < read;
+        }
+    }
+}
+
+/// Kill the command together with any process it started
+fn kill(child: &mut Child) {
+    if kill_process_group(child) {
+        return;
+    }
+    if let Err(e) = child.kill() {
+        log_warning("Failed to kill command", &e.to_string());
+    }
+}
+
+/// Kill every process in the command's process group; returns false if there
+/// was none left or process groups are not supported
+fn kill_process_group(child: &Child) -> bool {
+    #[cfg(unix)]
+    {
+        // The command leads its own process group, see `apply_limits`
+        // SAFETY: kill has no memory-safety preconditions
+        unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = child;
+        false
+    }
+}
+
+/// Put the command in its own process group and apply the configured resource limits
+#[cfg(unix)]
+fn apply_limits(command: &mut Command, config: &CommandConfig) {
+    use std::os::unix::process::CommandExt;
+
+    command.process_group(0);
+    let memory = config.max_memory_mb.saturating_mul(1024 * 1024);
+    let cpu = config.max_cpu_sec;
+    if memory == 0 && cpu == 0 {
+        return;
+    }
+    // The hard CPU limit is one second above the soft one, so the command gets
+    // SIGXCPU (reported as such) rather than an anonymous SIGKILL
+    let limits = [(libc::RLIMIT_AS, memory, memory), (libc::RLIMIT_CPU, cpu, cpu.saturating_add(1))];
+    // SAFETY: the closure runs in the forked child before exec and only calls
+    // setrlimit, which is async-signal-safe, without allocating
+    unsafe {
+        command.pre_exec(move || {
+            for (resource, soft, hard) in limits {
+                if soft == 0 {
+                    continue;
+                }
+                let limit = libc::rlimit { rlim_cur: soft as libc::rlim_t, rlim_max: hard as libc::rlim_t };
+                if libc::setrlimit(resource, &limit) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Describe how a command that did not exit normally was ended
+fn describe_termination(status: ExitStatus) -> String {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return format!("was terminated by signal {}", signal);
+        }
+    }
+    format!("ended without an exit code ({})", status)
+}
+
+/// Private temporary directory for the `{input}` and `{output}` files of one run,
+/// removed when dropped
+struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    fn create() -> io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("cfp-command-{}", uuid::Uuid::new_v4()));
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(&path)?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.0) {
+            log_warning("Failed to remove temporary command directory", &format!("{}: {}", self.0.display(), e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reader returning `data` in `chunk`-sized reads, interrupted before every read
+    struct Chunked<'a> {
+        data: &'a [u8],
+        chunk: usize,
+        interrupt: bool,
+    }
+
+    impl Read for Chunked<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let read = self.chunk.min(buffer.len()).min(self.data.len());
+            buffer[..read].copy_from_slice(&self.data[..read]);
+            self.data = &self.data[read..];
+            Ok(read)
+        }
+    }
+
+    fn chunked(data: &[u8]) -> Chunked<'_> {
+        Chunked { data, chunk: 3, interrupt: false }
+    }
+
+    #[test]
+    fn env_entries_need_a_key() {
+        assert_eq!(parse_env_entry("MODE=fast"), Some(("MODE", "fast")));
+        assert_eq!(parse_env_entry("URL=a=b"), Some(("URL", "a=b")));
+        assert_eq!(parse_env_entry("EMPTY="), Some(("EMPTY", "")));
+        assert_eq!(parse_env_entry("=value"), None);
+        assert_eq!(parse_env_entry("MODE"), None);
+    }
+
+    #[test]
+    fn read_limited_keeps_the_head_or_the_tail() {
+        assert_eq!(read_limited(chunked(b"abcdefgh"), 5, false).unwrap(), (b"abcde".to_vec(), true));
+        assert_eq!(read_limited(chunked(b"abcdefgh"), 5, true).unwrap(), (b"defgh".to_vec(), true));
+        assert_eq!(read_limited(chunked(b"abc"), 5, false).unwrap(), (b"abc".to_vec(), false));
+        assert_eq!(read_limited(chunked(b"abcde"), 5, true).unwrap(), (b"abcde".to_vec(), false));
+    }
+
+    #[test]
+    fn rejects_invalid_configuration() {
+        let config = |argv: &[&str], env: &[&str]| CommandConfig {
+            argv: argv.iter().map(ToString::to_string).collect(),
+            env: env.iter().map(ToString::to_string).collect(),
+            ..CommandConfig::default()
+        };
+        assert!(matches!(CommandStrategy::new(&config(&[], &[])), Err(AppError::Config { .. })));
+        assert!(matches!(CommandStrategy::new(&config(&[""], &[])), Err(AppError::Config { .. })));
+        assert!(matches!(CommandStrategy::new(&config(&["cat"], &["NOVALUE"])), Err(AppError::Config { .. })));
+        assert!(CommandStrategy::new(&config(&["cat"], &["MODE=fast"])).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_the_command_on_the_content() {
+        let run = |argv: &[&str], file_name: &str| {
+            let config = CommandConfig { argv: argv.iter().map(ToString::to_string).collect(), ..CommandConfig::default() };
+            CommandStrategy::new(&config).unwrap().process_content(file_name, b"hello")
+        };
+        assert_eq!(run(&["tr", "a-z", "A-Z"], "a.txt").unwrap(), "HELLO");
+        assert_eq!(run(&["cp", "{input}", "{output}"], "a.txt").unwrap(), "hello");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn shell_scripts_get_file_names_from_the_environment() {
+        let config = CommandConfig {
+            argv: ["sh", "-c", r#"cat "$CFP_INPUT" > "$CFP_OUTPUT"; printf '%s' "$CFP_NAME" >> "$CFP_OUTPUT""#].map(String::from).to_vec(),
+            input_file: true,
+            output_file: true,
+            ..CommandConfig::default()
+        };
+        let strategy = CommandStrategy::new(&config).unwrap();
+        // The name is never parsed by the shell, so it cannot run anything
+        let name = "a;touch injected;$(touch injected).csv";
+        assert_eq!(strategy.process_content(name, b"hello").unwrap(), format!("hello{}", name));
+        assert!(!Path::new("injected").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exit_codes_map_to_error_kinds() {
+        let run = |script: &str| {
+            let config = CommandConfig { argv: ["sh", "-c", script].map(String::from).to_vec(), ..CommandConfig::default() };
+            CommandStrategy::new(&config).unwrap().process_content("a.txt", b"hello").unwrap_err()
+        };
+
+        let error = run("echo 'service down' >&2; exit 75");
+        assert!(matches!(error, AppError::Unavailable { .. }), "{}", error);
+        assert!(error.is_retryable());
+        assert!(error.to_string().contains("exited with code 75; stderr: service down"), "{}", error);
+
+        let error = run("exit 65");
+        assert!(matches!(error, AppError::Rejected { .. }), "{}", error);
+        assert!(!error.is_retryable());
+
+        let error = run("echo broken >&2; exit 3");
+        assert!(matches!(error, AppError::Strategy { .. }), "{}", error);
+        assert!(error.to_string().contains("exited with code 3; stderr: broken"), "{}", error);
+    }
+
+    #[test]
+    fn finds_placeholders_inside_shell_scripts() {
+        let argv = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(templated_shell_script(&argv(&["sh", "-c", "cat {input}"])), Some("cat {input}"));
+        assert_eq!(templated_shell_script(&argv(&["/usr/bin/bash", "--norc", "-euc", "echo {name}"])), Some("echo {name}"));
+        assert_eq!(templated_shell_script(&argv(&["sh", "-c", "cat \"$1\"", "sh", "{input}"])), None);
+        assert_eq!(templated_shell_script(&argv(&["jq", "-c", "{name: .a}"])), None);
+        assert_eq!(templated_shell_script(&[]), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn background_processes_do_not_keep_the_run_alive() {
+        let config = CommandConfig {
+            argv: vec!["sh".to_string(), "-c".to_string(), "sleep 30 & cat".to_string()],
+            timeout_sec: 10,
+            ..CommandConfig::default()
+        };
+        let started = Instant::now();
+        let output = CommandStrategy::new(&config).unwrap().process_content("a.txt", b"hello").unwrap();
+        assert_eq!(output, "hello");
+        assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+    }
+}
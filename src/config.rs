@@ -1,10 +1,61 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use crate::error::AppError;
-use crate::logging::{log_info, log_error};
+use crate::logging::log_error;
+
+/// Prefix of environment variables overriding configuration fields,
+/// e.g. `CFP_DIRECTORIES__INPUT_DIR` overrides `directories.input_dir`
+pub const ENV_PREFIX: &str = "CFP_";
+
+/// File name of the configuration file in every search location
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// Directory name used below the user and system configuration directories
+const CONFIG_DIR_NAME: &str = "concurrent_file_processor";
+
+/// Commented configuration file with the default settings, written by `config init`
+pub const CONFIG_TEMPLATE: &str = include_str!("../config.example.toml");
+
+/// Where the effective value of a configuration field came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Built-in default
+    Default,
+    /// Configuration file
+    File(PathBuf),
+    /// Environment variable
+    Env(String),
+    /// Command-line option
+    Cli,
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(path) => write!(f, "file {}", path.display()),
+            ConfigSource::Env(var) => write!(f, "env {}", var),
+            ConfigSource::Cli => write!(f, "command line"),
+        }
+    }
+}
+
+/// Effective configuration together with the source of every field
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    pub config: AppConfig,
+    /// Source of each field, keyed by its dotted path (e.g. `directories.input_dir`)
+    pub sources: BTreeMap<String, ConfigSource>,
+    /// Problems that did not prevent loading, reported once logging is set up
+    pub warnings: Vec<String>,
+    /// Configuration file the values were read from, if any
+    pub path: Option<PathBuf>,
+}
 
 /// Application configuration loaded from TOML file
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AppConfig {
     /// File processing configuration
     pub processing: ProcessingConfig,
@@ -14,10 +65,23 @@ pub struct AppConfig {
     pub logging: LoggingConfig,
     /// File watcher configuration
     pub watcher: WatcherConfig,
+    /// Processing trigger configuration
+    pub trigger: TriggerConfig,
+    /// Job queue configuration
+    pub queue: QueueConfig,
+    /// Configuration hot-reload settings
+    pub reload: ReloadConfig,
+    /// HTTP endpoint settings
+    pub http: HttpConfig,
+    /// Liveness and readiness thresholds
+    pub health: HealthConfig,
+    /// Webhook notifications about finished jobs
+    pub webhook: WebhookConfig,
 }
 
 /// File processing configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ProcessingConfig {
     /// Maximum number of retries for file operations
     pub max_retries: u32,
@@ -25,14 +89,91 @@ pub struct ProcessingConfig {
     pub initial_retry_delay_ms: u64,
     /// Maximum retry delay in seconds
     pub max_retry_delay_sec: u64,
+    /// Times a job is run again after a retryable error
+    pub job_retries: u32,
     /// Output file extension
     pub output_extension: String,
     /// File locked error code for Windows
     pub file_locked_error_code: i32,
+    /// Name of the processing strategy (hash, text_analysis, command, script, or a WebAssembly plugin)
+    pub strategy: String,
+    /// Write a JSON sidecar describing the result next to each output file
+    pub write_sidecar: bool,
+    /// Append every result as one JSON line to `manifest_file`
+    pub write_manifest: bool,
+    /// File name of the result manifest in the output directory
+    pub manifest_file: String,
+    /// External command run by the `command` strategy
+    pub command: CommandConfig,
+    /// WebAssembly plugins selectable as strategies
+    pub wasm: WasmConfig,
+    /// Rhai script run by the `script` strategy
+    pub script: ScriptConfig,
+}
+
+/// Rhai script run for every file by the `script` processing strategy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptConfig {
+    /// Path of the script defining `fn process(name, content)`
+    pub path: String,
+    /// Operations the script may perform per file (0 for no limit)
+    pub max_operations: u64,
+    /// Longest string the script may build, in megabytes (0 for no limit)
+    pub max_string_mb: u64,
+    /// Most items an array or object map built by the script may hold (0 for no limit)
+    pub max_collection_items: usize,
+    /// Deepest nesting of function calls, e.g. through recursion
+    pub max_call_levels: usize,
+}
+
+/// WebAssembly plugins, selected with `strategy = "<plugin name>"`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WasmConfig {
+    /// Directory holding the plugins as `<name>.wasm`
+    pub plugins_dir: String,
+    /// Fuel a plugin may consume per file, roughly one unit per instruction (0 for no limit)
+    pub fuel: u64,
+    /// Linear memory a plugin may use per file, in megabytes
+    pub max_memory_mb: u64,
+}
+
+/// External command run for every file by the `command` processing strategy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CommandConfig {
+    /// Program and its arguments; `{input}`, `{output}` and `{name}` are replaced for every file
+    pub argv: Vec<String>,
+    /// Write the content to a temporary file (`{input}`, `$CFP_INPUT`) instead of piping it to stdin
+    pub input_file: bool,
+    /// Read the output from a temporary file (`{output}`, `$CFP_OUTPUT`) instead of stdout
+    pub output_file: bool,
+    /// Additional environment variables as `KEY=VALUE`
+    pub env: Vec<String>,
+    /// Start the command with only the variables in `env` instead of inheriting the service's
+    pub clear_env: bool,
+    /// Working directory of the command; empty uses the service's
+    pub working_dir: String,
+    /// Seconds after which the command is killed (0 waits indefinitely)
+    pub timeout_sec: u64,
+    /// Exit codes that count as success
+    pub success_codes: Vec<i32>,
+    /// Exit codes reporting a temporary failure; the job is retried like other retryable errors
+    pub retry_codes: Vec<i32>,
+    /// Exit codes reporting that the file itself is invalid; the job fails without retries
+    pub reject_codes: Vec<i32>,
+    /// Largest output accepted from the command, in megabytes
+    pub max_output_mb: u64,
+    /// Address space limit of the command in megabytes (0 for no limit; Unix only)
+    pub max_memory_mb: u64,
+    /// CPU time limit of the command in seconds (0 for no limit; Unix only)
+    pub max_cpu_sec: u64,
 }
 
 /// Directory configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct DirectoryConfig {
     /// Input directory path
     pub input_dir: String,
@@ -42,6 +183,7 @@ pub struct DirectoryConfig {
 
 /// Logging configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LoggingConfig {
     /// Log level (debug, info, warn, error)
     pub level: String,
@@ -51,10 +193,56 @@ pub struct LoggingConfig {
     pub log_basename: String,
     /// Whether to duplicate logs to stdout
     pub duplicate_to_stdout: bool,
+    /// Format of log lines (free text or one JSON object per line)
+    pub format: LogFormat,
+    /// Most verbose level duplicated to stdout
+    pub stdout_level: StdoutLevel,
+    /// Size in megabytes at which the log file is rotated (0 disables size-based rotation)
+    pub rotate_size_mb: u64,
+    /// Age at which the log file is rotated
+    pub rotate_age: RotationAge,
+    /// Number of rotated log files to keep; older ones are deleted (0 keeps all and deletes nothing)
+    pub keep_files: usize,
+    /// Whether to gzip rotated log files
+    pub compress: bool,
+}
+
+/// Most verbose level duplicated to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StdoutLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+    /// Every line written to the log file
+    All,
+}
+
+/// Age at which the log file is rotated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RotationAge {
+    /// Rotate by size only
+    Never,
+    Hour,
+    Day,
+}
+
+/// Format of log lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// `LEVEL [module] context | message`
+    Text,
+    /// One JSON object per line with timestamp, level, context, message and structured fields
+    Json,
 }
 
 /// File watcher configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct WatcherConfig {
     /// Channel buffer size for watcher events
     pub channel_buffer_size: usize,
@@ -62,27 +250,252 @@ pub struct WatcherConfig {
     pub processing_delay_ms: u64,
     /// Whether to watch directories recursively
     pub recursive: bool,
+    /// Interval in seconds between reconciliation rescans of the input directory (0 disables)
+    pub rescan_interval_sec: u64,
+    /// Initial delay in milliseconds before re-creating a failed watcher
+    pub restart_initial_delay_ms: u64,
+    /// Maximum delay in seconds between watcher re-creation attempts
+    pub restart_max_delay_sec: u64,
+    /// Watcher backend (native OS notifications or polling)
+    pub backend: WatcherBackend,
+    /// Polling interval in milliseconds for the poll backend
+    pub poll_interval_ms: u64,
+    /// Whether to fall back to polling if the native watcher fails to initialize
+    pub fallback_to_poll: bool,
 }
 
-impl Default for AppConfig {
-    fn default() -> Self {
-        Self {
-            processing: ProcessingConfig::default(),
-            directories: DirectoryConfig::default(),
-            logging: LoggingConfig::default(),
-            watcher: WatcherConfig::default(),
+/// File watcher backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatcherBackend {
+    /// OS notifications (inotify, FSEvents, ReadDirectoryChangesW)
+    Native,
+    /// Periodic directory polling, for network and container filesystems
+    Poll,
+}
+
+/// Job queue configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueueConfig {
+    /// Maximum number of queued files; further events are dropped and picked up by a rescan
+    pub capacity: usize,
+    /// Queue depth at which a warning is logged and below which a spilled queue is rescanned
+    pub high_water_mark: usize,
+    /// Number of files processed concurrently
+    pub workers: usize,
+    /// Interval in seconds between queue statistics log lines (0 disables)
+    pub stats_log_interval_sec: u64,
+    /// Order of queued files with equal priority
+    pub ordering: QueueOrdering,
+    /// Prefix of a priority encoded in a file or directory name (e.g. `priority-5_report.csv`); empty disables
+    pub path_priority_prefix: String,
+    /// Priorities assigned to files whose name matches a glob pattern
+    pub priority_rules: Vec<PriorityRule>,
+}
+
+/// Order of queued files with equal priority
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueOrdering {
+    /// Arrival order
+    Fifo,
+    /// Smallest files first
+    SmallestFirst,
+    /// Files with the oldest modification time first
+    OldestFirst,
+}
+
+/// Priority assigned to files matching a glob pattern; higher runs first.
+/// Unknown fields are rejected: a misspelled field would silently change which files match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PriorityRule {
+    /// Glob pattern matched against the file name
+    pub pattern: String,
+    /// Priority of matching files
+    pub priority: i64,
+}
+
+/// Processing trigger configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TriggerConfig {
+    /// When a detected file becomes ready for processing
+    pub mode: TriggerMode,
+    /// Suffix of the per-file marker signalling that a file is complete (e.g. `data.csv.done`)
+    pub marker_suffix: String,
+    /// Name of the directory-level marker releasing every file in its folder as one batch
+    pub batch_marker: String,
+}
+
+/// When a detected file becomes ready for processing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TriggerMode {
+    /// Process files as soon as they are detected
+    Immediate,
+    /// Process files only once their marker file (or a batch marker) appears
+    Marker,
+}
+
+/// Configuration hot-reload settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReloadConfig {
+    /// Whether the service watches its configuration file and applies changes while running
+    pub enabled: bool,
+    /// Delay in milliseconds to wait for further changes before reloading
+    pub debounce_ms: u64,
+}
+
+/// HTTP endpoint settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Whether the service serves HTTP endpoints
+    pub enabled: bool,
+    /// Address and port to listen on
+    pub listen: String,
+    /// Whether to serve Prometheus metrics at `/metrics`
+    pub metrics: bool,
+    /// Whether to serve liveness and readiness probes under `/health`
+    pub health: bool,
+    /// Whether to serve the admin API under `/admin`
+    pub admin: bool,
+    /// Bearer token required by the admin API; the API refuses every request while it is empty
+    pub admin_token: String,
+}
+
+/// Webhook notifications about finished jobs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Endpoints that receive a JSON POST for every matching event
+    pub targets: Vec<WebhookTarget>,
+    /// Timeout of a single delivery attempt in milliseconds
+    pub timeout_ms: u64,
+    /// Attempts per delivery before it is given up
+    pub max_attempts: u32,
+    /// Delay in milliseconds before the first retry; doubled for every further retry
+    pub retry_delay_ms: u64,
+    /// Longest delay in milliseconds between two retries
+    pub max_retry_delay_ms: u64,
+    /// Seconds `once`, `process` and a graceful shutdown wait for pending deliveries
+    pub shutdown_timeout_sec: u64,
+}
+
+/// An endpoint notified about finished jobs.
+/// Unknown fields are rejected: a misspelled `secret` would silently send unsigned requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookTarget {
+    /// `http://` or `https://` URL to POST to
+    pub url: String,
+    /// Key of the HMAC-SHA256 signature header; empty sends unsigned requests
+    #[serde(default)]
+    pub secret: String,
+    /// Events sent to this target
+    #[serde(default = "all_webhook_events")]
+    pub events: Vec<WebhookEvent>,
+    /// Glob patterns matched against the input file name; empty matches every file
+    #[serde(default)]
+    pub files: Vec<String>,
+}
+
+/// Kind of job outcome a webhook is sent for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookEvent {
+    /// The file was processed and its output written
+    Completed,
+    /// Processing the file failed; it stays in the input directory
+    Failed,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::Completed => "completed",
+            WebhookEvent::Failed => "failed",
         }
     }
 }
 
+fn all_webhook_events() -> Vec<WebhookEvent> {
+    vec![WebhookEvent::Completed, WebhookEvent::Failed]
+}
+
+/// Liveness and readiness thresholds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthConfig {
+    /// Seconds the watcher may keep restarting before the service is reported as not alive
+    pub watcher_grace_sec: u64,
+    /// Seconds without a successful job, while files are waiting, before the
+    /// service is reported as not ready (0 disables)
+    pub stalled_after_sec: u64,
+}
+
 impl Default for ProcessingConfig {
     fn default() -> Self {
         Self {
             max_retries: 10,
             initial_retry_delay_ms: 100,
             max_retry_delay_sec: 2,
+            job_retries: 2,
             output_extension: ".processed.txt".to_string(),
             file_locked_error_code: 32,
+            strategy: "text_analysis".to_string(),
+            write_sidecar: false,
+            write_manifest: false,
+            manifest_file: "manifest.jsonl".to_string(),
+            command: CommandConfig::default(),
+            wasm: WasmConfig::default(),
+            script: ScriptConfig::default(),
+        }
+    }
+}
+
+impl Default for ScriptConfig {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            max_operations: 100_000_000,
+            max_string_mb: 64,
+            max_collection_items: 1_000_000,
+            max_call_levels: 64,
+        }
+    }
+}
+
+impl Default for WasmConfig {
+    fn default() -> Self {
+        Self {
+            plugins_dir: "./plugins".to_string(),
+            fuel: 1_000_000_000,
+            max_memory_mb: 64,
+        }
+    }
+}
+
+impl Default for CommandConfig {
+    fn default() -> Self {
+        Self {
+            argv: Vec::new(),
+            input_file: false,
+            output_file: false,
+            env: Vec::new(),
+            clear_env: false,
+            working_dir: String::new(),
+            timeout_sec: 60,
+            success_codes: vec![0],
+            // EX_TEMPFAIL and EX_DATAERR from sysexits.h
+            retry_codes: vec![75],
+            reject_codes: vec![65],
+            max_output_mb: 64,
+            max_memory_mb: 0,
+            max_cpu_sec: 0,
         }
     }
 }
@@ -103,6 +516,12 @@ impl Default for LoggingConfig {
             log_dir: "logs".to_string(),
             log_basename: "app_log".to_string(),
             duplicate_to_stdout: true,
+            format: LogFormat::Text,
+            stdout_level: StdoutLevel::Info,
+            rotate_size_mb: 100,
+            rotate_age: RotationAge::Day,
+            keep_files: 0,
+            compress: false,
         }
     }
 }
@@ -113,55 +532,140 @@ impl Default for WatcherConfig {
             channel_buffer_size: 32,
             processing_delay_ms: 50,
             recursive: true,
+            rescan_interval_sec: 60,
+            restart_initial_delay_ms: 500,
+            restart_max_delay_sec: 30,
+            backend: WatcherBackend::Native,
+            poll_interval_ms: 2000,
+            fallback_to_poll: true,
         }
     }
 }
 
-impl AppConfig {
-    /// Load configuration from a TOML file
-    pub fn from_file(path: &PathBuf) -> Result<Self, AppError> {
-        let config_content = std::fs::read_to_string(path).map_err(|e| {
-            log_error("Failed to read config file", &format!("{}: {}", path.display(), e));
-            AppError::Io(e)
-        })?;
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 10000,
+            high_water_mark: 8000,
+            workers: 8,
+            stats_log_interval_sec: 60,
+            ordering: QueueOrdering::Fifo,
+            path_priority_prefix: String::new(),
+            priority_rules: Vec::new(),
+        }
+    }
+}
 
-        let config: AppConfig = toml::from_str(&config_content).map_err(|e| {
-            log_error("Failed to parse config file", &format!("{}: {}", path.display(), e));
-            AppError::Processing(format!("Invalid TOML configuration: {}", e))
-        })?;
+impl Default for TriggerConfig {
+    fn default() -> Self {
+        Self {
+            mode: TriggerMode::Immediate,
+            marker_suffix: ".done".to_string(),
+            batch_marker: "_SUCCESS".to_string(),
+        }
+    }
+}
 
-        log_info("Configuration loaded successfully", &format!("from {}", path.display()));
-        Ok(config)
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            debounce_ms: 500,
+        }
     }
+}
 
-    /// Load configuration from default location or create default config
-    pub fn load_or_default() -> Result<Self, AppError> {
-        let config_path = PathBuf::from("config.toml");
-        
-        if config_path.exists() {
-            Self::from_file(&config_path)
-        } else {
-            log_info("No config file found", "Creating default configuration");
-            let config = AppConfig::default();
-            config.save_to_file(&config_path)?;
-            Ok(config)
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            targets: Vec::new(),
+            timeout_ms: 5000,
+            max_attempts: 3,
+            retry_delay_ms: 1000,
+            max_retry_delay_ms: 30_000,
+            shutdown_timeout_sec: 30,
         }
     }
+}
 
-    /// Save configuration to a TOML file
-    pub fn save_to_file(&self, path: &PathBuf) -> Result<(), AppError> {
-        let config_content = toml::to_string_pretty(self).map_err(|e| {
-            log_error("Failed to serialize config", &e);
-            AppError::Processing(format!("Failed to serialize configuration: {}", e))
-        })?;
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            watcher_grace_sec: 60,
+            stalled_after_sec: 0,
+        }
+    }
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            listen: "127.0.0.1:9898".to_string(),
+            metrics: true,
+            health: true,
+            admin: false,
+            admin_token: String::new(),
+        }
+    }
+}
+
+impl AppConfig {
+    /// Load configuration layered as defaults < file < environment < command line.
+    /// Without an explicit `path` the first file found by `config_search_paths` is used;
+    /// if there is none, the built-in defaults are used and nothing is written to disk.
+    /// `cli_overrides` are dotted field paths with raw values, parsed like environment values.
+    pub fn load_layered(path: Option<&Path>, cli_overrides: &[(String, String)]) -> Result<LoadedConfig, AppError> {
+        let (mut value, file_source) = match path {
+            Some(path) => (read_toml_table(path)?, ConfigSource::File(path.to_path_buf())),
+            None => match discover_config_file() {
+                Some(found) => (read_toml_table(&found)?, ConfigSource::File(found)),
+                None => (to_toml_table(&AppConfig::default())?, ConfigSource::Default),
+            },
+        };
 
-        std::fs::write(path, config_content).map_err(|e| {
-            log_error("Failed to write config file", &format!("{}: {}", path.display(), e));
-            AppError::Io(e)
+        let config_path = match &file_source {
+            ConfigSource::File(path) => Some(path.clone()),
+            _ => None,
+        };
+
+        let schema: BTreeMap<String, toml::Value> = flatten_table(&to_toml_table(&AppConfig::default())?).into_iter().collect();
+
+        // Missing sections and fields fall back to their defaults; unknown ones are reported
+        let mut warnings = Vec::new();
+        let mut sources = BTreeMap::new();
+        for (key, _) in flatten_table(&value) {
+            if !schema.contains_key(&key) {
+                let suggestion = suggest_field(&key, &schema).map(|known| format!(" (did you mean {}?)", known)).unwrap_or_default();
+                warnings.push(format!("Unknown configuration field {} in {}{}", key, file_source, suggestion));
+            }
+            sources.insert(key, file_source.clone());
+        }
+
+        let mut env_vars: Vec<(String, String)> = std::env::vars()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect();
+        env_vars.sort();
+        for (name, raw) in env_vars {
+            let key = name[ENV_PREFIX.len()..].split("__").map(|part| part.to_lowercase()).collect::<Vec<_>>().join(".");
+            if !schema.contains_key(&key) {
+                let suggestion = suggest_field(&key, &schema).map(|known| format!(" (did you mean {}?)", env_var_name(known))).unwrap_or_default();
+                warnings.push(format!("Unknown configuration environment variable {}{}", name, suggestion));
+                continue;
+            }
+            apply_override(&mut value, &schema, &key, &raw, ConfigSource::Env(name), &mut sources)?;
+        }
+
+        for (key, raw) in cli_overrides {
+            apply_override(&mut value, &schema, key, raw, ConfigSource::Cli, &mut sources)?;
+        }
+
+        let config: AppConfig = toml::Value::Table(value).try_into().map_err(|e| {
+            log_error("Failed to parse configuration", &e);
+            AppError::Config { message: "Invalid configuration".to_string(), source: Some(e.into()) }
         })?;
 
-        log_info("Configuration saved", &format!("to {}", path.display()));
-        Ok(())
+        Ok(LoadedConfig { config, sources, warnings, path: config_path })
     }
 
     /// Get input directory as PathBuf
@@ -178,4 +682,243 @@ impl AppConfig {
     pub fn log_dir(&self) -> PathBuf {
         PathBuf::from(&self.logging.log_dir)
     }
-} 
\ No newline at end of file
+}
+
+impl LoadedConfig {
+    /// Fail if loading reported any warning, such as an unknown field (strict mode)
+    pub fn deny_warnings(&self) -> Result<(), AppError> {
+        if self.warnings.is_empty() {
+            return Ok(());
+        }
+        let details = self.warnings.iter().map(|warning| format!("  {}", warning)).collect::<Vec<_>>().join("\n");
+        Err(AppError::config(format!("Configuration warnings are errors in strict mode:\n{}", details)))
+    }
+
+    /// Describe every effective field value and where it came from, one per line
+    pub fn describe_sources(&self) -> Result<String, AppError> {
+        let values = flatten_table(&to_toml_table(&self.config)?);
+        Ok(values.into_iter().map(|(key, value)| {
+            let source = self.sources.get(&key).cloned().unwrap_or(ConfigSource::Default);
+            format!("{} = {}  # {}", key, value, source)
+        }).collect::<Vec<_>>().join("\n"))
+    }
+}
+
+/// Locations searched for a configuration file when none is given, in order:
+/// the working directory, the user configuration directory
+/// (`$XDG_CONFIG_HOME` or `~/.config`) and `/etc/concurrent_file_processor`
+pub fn config_search_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from(CONFIG_FILE_NAME)];
+
+    let user_config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").filter(|dir| !dir.is_empty()).map(|home| PathBuf::from(home).join(".config")));
+    if let Some(dir) = user_config_dir {
+        paths.push(dir.join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME));
+    }
+
+    paths.push(Path::new("/etc").join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME));
+    paths
+}
+
+/// First existing configuration file in the search locations
+pub fn discover_config_file() -> Option<PathBuf> {
+    config_search_paths().into_iter().find(|path| path.is_file())
+}
+
+/// Write the commented default configuration to `path`, creating missing parent directories
+pub fn write_config_template(path: &Path) -> Result<(), AppError> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, CONFIG_TEMPLATE)?;
+    Ok(())
+}
+
+/// Read a TOML file into a table without interpreting it
+fn read_toml_table(path: &Path) -> Result<toml::Table, AppError> {
+    let config_content = std::fs::read_to_string(path).map_err(|e| {
+        log_error("Failed to read config file", &format!("{}: {}", path.display(), e));
+        AppError::file("read", path, e)
+    })?;
+
+    config_content.parse::<toml::Table>().map_err(|e| {
+        log_error("Failed to parse config file", &format!("{}: {}", path.display(), e));
+        AppError::Config { message: "Invalid TOML configuration".to_string(), source: Some(e.into()) }
+    })
+}
+
+pub(crate) fn to_toml_table(config: &AppConfig) -> Result<toml::Table, AppError> {
+    toml::Table::try_from(config).map_err(|e| {
+        AppError::Config { message: "Failed to serialize configuration".to_string(), source: Some(e.into()) }
+    })
+}
+
+/// Flatten nested tables into dotted keys; arrays and scalars are leaves
+pub(crate) fn flatten_table(table: &toml::Table) -> Vec<(String, toml::Value)> {
+    let mut fields = Vec::new();
+    for (key, value) in table {
+        match value {
+            toml::Value::Table(inner) => {
+                fields.extend(flatten_table(inner).into_iter().map(|(inner_key, v)| (format!("{}.{}", key, inner_key), v)));
+            }
+            _ => fields.push((key.clone(), value.clone())),
+        }
+    }
+    fields
+}
+
+/// Closest known field to an unknown one: a similarly spelled field, or a field
+/// with the same name in another section
+fn suggest_field<'a>(key: &str, schema: &'a BTreeMap<String, toml::Value>) -> Option<&'a str> {
+    let max_distance = (key.len() / 5).max(2);
+    let similar = schema.keys()
+        .map(|known| (strsim::damerau_levenshtein(key, known), known))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known.as_str());
+
+    let field_name = key.rsplit('.').next();
+    similar.or_else(|| schema.keys().map(String::as_str).find(|known| known.rsplit('.').next() == field_name))
+}
+
+/// Environment variable overriding a dotted field path, e.g. `CFP_QUEUE__WORKERS`
+fn env_var_name(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.replace('.', "__").to_uppercase())
+}
+
+/// Parse `raw` according to the type of the field in `schema` and set it in `table`
+fn apply_override(
+    table: &mut toml::Table,
+    schema: &BTreeMap<String, toml::Value>,
+    key: &str,
+    raw: &str,
+    source: ConfigSource,
+    sources: &mut BTreeMap<String, ConfigSource>
+) -> Result<(), AppError> {
+    let invalid = |e: &dyn std::fmt::Display| {
+        AppError::config(format!("Invalid value for {} from {}: {}", key, source, e))
+    };
+
+    let value = match schema.get(key) {
+        Some(toml::Value::String(_)) => toml::Value::String(raw.to_string()),
+        Some(toml::Value::Integer(_)) => toml::Value::Integer(raw.trim().parse().map_err(|e| invalid(&e))?),
+        Some(toml::Value::Float(_)) => toml::Value::Float(raw.trim().parse().map_err(|e| invalid(&e))?),
+        Some(toml::Value::Boolean(_)) => toml::Value::Boolean(raw.trim().parse().map_err(|e| invalid(&e))?),
+        Some(_) => {
+            // Arrays and other structured values use TOML inline syntax
+            let mut parsed = format!("value = {}", raw).parse::<toml::Table>().map_err(|e| invalid(&e))?;
+            parsed.remove("value").ok_or_else(|| invalid(&"missing value"))?
+        }
+        None => return Err(AppError::config(format!("Unknown configuration field {} from {}", key, source))),
+    };
+
+    let mut segments: Vec<&str> = key.split('.').collect();
+    let field = segments.pop().unwrap_or(key);
+    let mut current = table;
+    for segment in segments {
+        current = current
+            .entry(segment.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| invalid(&format!("{} is not a section", segment)))?;
+    }
+    current.insert(field.to_string(), value);
+    sources.insert(key.to_string(), source);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Load `source` as the configuration file, without environment or command line overrides
+    fn load(source: &str) -> Result<LoadedConfig, AppError> {
+        let path = std::env::temp_dir().join(format!("cfp-config-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, source).unwrap();
+        let loaded = AppConfig::load_layered(Some(&path), &[]);
+        std::fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    fn schema() -> BTreeMap<String, toml::Value> {
+        flatten_table(&to_toml_table(&AppConfig::default()).unwrap()).into_iter().collect()
+    }
+
+    #[test]
+    fn flatten_table_uses_dotted_keys_and_keeps_lists_whole() {
+        let table: toml::Table = "top = 1\n[a]\nlist = [1, 2]\n[a.b]\nc = \"x\"\n".parse().unwrap();
+        let fields: Vec<(String, String)> = flatten_table(&table).into_iter().map(|(key, value)| (key, value.to_string())).collect();
+        assert_eq!(fields, [
+            ("a.b.c".to_string(), "\"x\"".to_string()),
+            ("a.list".to_string(), "[1, 2]".to_string()),
+            ("top".to_string(), "1".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn suggests_similar_fields_or_the_same_name_in_another_section() {
+        let schema = schema();
+        assert_eq!(suggest_field("directories.inptu_dir", &schema), Some("directories.input_dir"));
+        assert_eq!(suggest_field("queue.worker", &schema), Some("queue.workers"));
+        assert_eq!(suggest_field("watcher.workers", &schema), Some("queue.workers"));
+        assert_eq!(suggest_field("completely.unrelated", &schema), None);
+    }
+
+    #[test]
+    fn overrides_are_parsed_by_field_type() {
+        let schema = schema();
+        let mut table = toml::Table::new();
+        let mut sources = BTreeMap::new();
+        let mut apply = |key: &str, raw: &str| apply_override(&mut table, &schema, key, raw, ConfigSource::Cli, &mut sources);
+
+        apply("queue.workers", " 4 ").unwrap();
+        apply("http.enabled", "true").unwrap();
+        apply("directories.input_dir", " /data/in ").unwrap();
+        apply("queue.priority_rules", r#"[{ pattern = "urgent_*", priority = 10 }]"#).unwrap();
+        assert!(matches!(apply("queue.workers", "many"), Err(AppError::Config { .. })));
+        assert!(matches!(apply("queue.wrokers", "4"), Err(AppError::Config { .. })));
+
+        let config: AppConfig = toml::Value::Table(table).try_into().unwrap();
+        assert_eq!(config.queue.workers, 4);
+        assert!(config.http.enabled);
+        assert_eq!(config.directories.input_dir, " /data/in ", "strings are taken verbatim");
+        assert_eq!(config.queue.priority_rules[0].priority, 10);
+        assert_eq!(sources.get("queue.workers"), Some(&ConfigSource::Cli));
+        assert!(!sources.contains_key("queue.wrokers"));
+    }
+
+    #[test]
+    fn env_var_names_use_double_underscores_between_sections() {
+        assert_eq!(env_var_name("queue.workers"), "CFP_QUEUE__WORKERS");
+        assert_eq!(env_var_name("processing.command.timeout_sec"), "CFP_PROCESSING__COMMAND__TIMEOUT_SEC");
+    }
+
+    #[test]
+    fn template_matches_the_defaults() {
+        let template: AppConfig = toml::from_str(CONFIG_TEMPLATE).unwrap();
+        assert_eq!(to_toml_table(&template).unwrap(), to_toml_table(&AppConfig::default()).unwrap());
+    }
+
+    #[test]
+    fn unknown_fields_in_arrays_of_tables_are_errors() {
+        let error = load("[[webhook.targets]]\nurl = \"http://127.0.0.1/hook\"\nsecrt = \"key\"\n").err().unwrap();
+        assert!(format!("{:#}", error).contains("unknown field `secrt`"), "{:#}", error);
+
+        let error = load("[[queue.priority_rules]]\npatern = \"urgent_*\"\npriority = 5\n").err().unwrap();
+        assert!(format!("{:#}", error).contains("unknown field `patern`"), "{:#}", error);
+
+        let loaded = load("[[webhook.targets]]\nurl = \"http://127.0.0.1/hook\"\nsecret = \"key\"\n").unwrap();
+        assert_eq!(loaded.config.webhook.targets[0].secret, "key");
+        assert!(loaded.warnings.is_empty(), "{:?}", loaded.warnings);
+    }
+
+    #[test]
+    fn unknown_fields_in_sections_are_warnings() {
+        let loaded = load("[directories]\ninptu_dir = \"/data/in\"\n").unwrap();
+        assert_eq!(loaded.warnings.len(), 1);
+        assert!(loaded.warnings[0].contains("did you mean directories.input_dir?"), "{}", loaded.warnings[0]);
+        assert!(loaded.deny_warnings().is_err());
+    }
+}
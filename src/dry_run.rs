@@ -0,0 +1,174 @@
+use crate::config::{AppConfig, TriggerConfig, TriggerMode};
+use crate::queue::JobQueue;
+use crate::trigger::resolve_ready_files;
+use crate::utils::{extract_file_name, output_path_for};
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// What the service would do with a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannedAction {
+    /// The file is ready and would be processed
+    Process,
+    /// The file is waiting for its ready marker and would be left alone
+    WaitForMarker,
+    /// The file is a ready marker
+    Marker,
+}
+
+impl PlannedAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlannedAction::Process => "process",
+            PlannedAction::WaitForMarker => "wait_for_marker",
+            PlannedAction::Marker => "marker",
+        }
+    }
+}
+
+/// One line of a dry-run plan
+#[derive(Debug, Serialize)]
+pub struct PlannedFile {
+    pub input: PathBuf,
+    pub action: PlannedAction,
+    pub priority: i64,
+    pub strategy: Option<String>,
+    pub output: Option<PathBuf>,
+    pub disposition: String,
+}
+
+/// Work out what processing `files` would do, without touching any of them.
+/// Entries are returned in the order the job queue would process them if the
+/// files arrived in the given order: by priority, then by `queue.ordering`.
+pub fn plan_files(files: Vec<PathBuf>, config: &AppConfig, trigger: &TriggerConfig) -> Vec<PlannedFile> {
+    let queue = JobQueue::new(&config.queue, &config.input_dir());
+    let output_dir = config.output_dir();
+
+    // Map every ready file to the marker (if any) that releases it
+    let mut ready: HashMap<PathBuf, Option<PathBuf>> = HashMap::new();
+    for file in &files {
+        if let Some(set) = resolve_ready_files(file, trigger) {
+            for ready_file in set.files {
+                ready.entry(ready_file).or_insert_with(|| set.marker.clone());
+            }
+        }
+    }
+
+    let mut plan: Vec<((u64, usize), PlannedFile)> = files.into_iter().enumerate().map(|(arrival, input)| {
+        let (priority, order_key) = queue.rank(&input);
+        let position = (order_key, arrival);
+
+        if let Some(marker) = ready.get(&input) {
+            let output = extract_file_name(&input).ok()
+                .map(|name| output_path_for(&name, &output_dir, &config.processing.output_extension));
+            let disposition = match marker {
+                Some(marker) => format!("delete original, consume marker {}", marker.display()),
+                None => "delete original".to_string(),
+            };
+            let strategy = Some(config.processing.strategy.clone());
+            return (position, PlannedFile { input, action: PlannedAction::Process, priority, strategy, output, disposition });
+        }
+
+        let (action, disposition) = if is_marker(&input, trigger) {
+            (PlannedAction::Marker, "consumed once its files are processed".to_string())
+        } else {
+            (PlannedAction::WaitForMarker, format!(
+                "keep until {}{} or {} appears",
+                input.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+                trigger.marker_suffix,
+                trigger.batch_marker
+            ))
+        };
+        (position, PlannedFile { input, action, priority, strategy: None, output: None, disposition })
+    }).collect();
+
+    plan.sort_by(|(a_position, a), (b_position, b)| {
+        (a.action != PlannedAction::Process).cmp(&(b.action != PlannedAction::Process))
+            .then_with(|| b.priority.cmp(&a.priority))
+            .then_with(|| a_position.cmp(b_position))
+    });
+    plan.into_iter().map(|(_, entry)| entry).collect()
+}
+
+fn is_marker(path: &std::path::Path, trigger: &TriggerConfig) -> bool {
+    if trigger.mode == TriggerMode::Immediate {
+        return false;
+    }
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    name == trigger.batch_marker || name.ends_with(&trigger.marker_suffix)
+}
+
+/// Render a plan as an aligned text table
+pub fn render_table(plan: &[PlannedFile]) -> String {
+    let rows: Vec<[String; 6]> = plan.iter().map(|entry| [
+        entry.action.as_str().to_string(),
+        entry.priority.to_string(),
+        entry.input.display().to_string(),
+        entry.strategy.clone().unwrap_or_else(|| "-".to_string()),
+        entry.output.as_ref().map(|p| p.display().to_string()).unwrap_or_else(|| "-".to_string()),
+        entry.disposition.clone(),
+    ]).collect();
+
+    let header = ["ACTION", "PRIORITY", "INPUT", "STRATEGY", "OUTPUT", "DISPOSITION"].map(String::from);
+    let mut widths = header.clone().map(|h| h.len());
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    std::iter::once(&header).chain(rows.iter())
+        .map(|row| {
+            row.iter().zip(widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PriorityRule, QueueOrdering};
+
+    /// Plan the files named `files` (created with the given sizes) in a fresh input directory
+    fn plan(files: &[(&str, usize)], configure: impl FnOnce(&mut AppConfig)) -> Vec<String> {
+        let input_dir = std::env::temp_dir().join(format!("cfp-dry-run-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&input_dir).unwrap();
+        let paths = files.iter().map(|(name, size)| {
+            let path = input_dir.join(name);
+            std::fs::write(&path, vec![b'x'; *size]).unwrap();
+            path
+        }).collect();
+
+        let mut config = AppConfig::default();
+        config.directories.input_dir = input_dir.to_string_lossy().into_owned();
+        configure(&mut config);
+        let trigger = config.trigger.clone();
+        let plan = plan_files(paths, &config, &trigger);
+        std::fs::remove_dir_all(&input_dir).unwrap();
+        plan.iter().map(|entry| entry.input.file_name().unwrap().to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn fifo_keeps_arrival_order_within_a_priority() {
+        let order = plan(&[("b.txt", 1), ("c.txt", 1), ("a.txt", 1)], |_| {});
+        assert_eq!(order, ["b.txt", "c.txt", "a.txt"]);
+    }
+
+    #[test]
+    fn follows_priority_then_queue_ordering() {
+        let order = plan(&[("large.txt", 30), ("small.txt", 10), ("medium.txt", 20), ("urgent.txt", 40)], |config| {
+            config.queue.ordering = QueueOrdering::SmallestFirst;
+            config.queue.priority_rules = vec![PriorityRule { pattern: "urgent*".to_string(), priority: 1 }];
+        });
+        assert_eq!(order, ["urgent.txt", "small.txt", "medium.txt", "large.txt"]);
+    }
+}
@@ -1,13 +1,45 @@
 use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::io;
 use crate::logging::log_error;
 
+/// Cause of an error raised by a library the application builds on
+pub type BoxError = Box<dyn Error + Send + Sync>;
+
 // Define custom error type for the application.
 // This allows for more granular error handling and reporting.
 #[derive(Debug)]
 pub enum AppError {
+    /// I/O error not tied to a particular file
     Io(io::Error),
+    /// The file watcher failed
     Watch(notify::Error),
+    /// An I/O operation on `path` failed, e.g. `operation` "read"
+    File { operation: &'static str, path: PathBuf, source: io::Error },
+    /// `path` does not exist (any more)
+    NotFound { path: PathBuf },
+    /// `path` stayed locked by another process for every attempt to open it
+    FileLocked { path: PathBuf, attempts: u32 },
+    /// Writing `path` failed because its file system is full
+    StorageFull { path: PathBuf, source: io::Error },
+    /// The strategy refused to process the file, e.g. because its content is invalid
+    Rejected { strategy: String, file: String, reason: String },
+    /// The strategy failed while processing the file
+    Strategy { strategy: String, file: String, message: String, source: Option<BoxError> },
+    /// The strategy could not process the file for now, e.g. a service it needs is down
+    Unavailable { strategy: String, file: String, message: String },
+    /// The strategy hit a configured resource limit
+    LimitExceeded { strategy: String, file: String, limit: String },
+    /// `operation` did not finish within `after`
+    Timeout { operation: String, after: Duration },
+    /// A network `operation` on `address` failed, e.g. `operation` "bind"
+    Network { operation: &'static str, address: String, source: io::Error },
+    /// A client sent an HTTP request that cannot be served
+    InvalidRequest(String),
+    /// The configuration is invalid
+    Config { message: String, source: Option<BoxError> },
+    /// Any other failure
     Processing(String),
 }
 
@@ -27,17 +59,53 @@ impl From<notify::Error> for AppError {
 }
 
 // Implement `Display` and `Error` traits for `AppError` to make it a proper error type.
+// Display describes this error only; its cause is available through `source()`, and
+// the alternate form `{:#}` appends the whole chain of causes.
 impl std::fmt::Display for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            AppError::Io(err) => write!(f, "IO error: {}", err),
-            AppError::Watch(err) => write!(f, "File watch error: {}", err),
+            AppError::Io(_) => write!(f, "IO error"),
+            AppError::Watch(_) => write!(f, "File watch error"),
+            AppError::File { operation, path, .. } => write!(f, "Failed to {} {}", operation, path.display()),
+            AppError::NotFound { path } => write!(f, "File not found: {}", path.display()),
+            AppError::FileLocked { path, attempts } => write!(f, "File {} is still locked after {} attempts", path.display(), attempts),
+            AppError::StorageFull { path, .. } => write!(f, "No space left to write {}", path.display()),
+            AppError::Rejected { strategy, file, reason } => write!(f, "Strategy '{}' rejected {}: {}", strategy, file, reason),
+            AppError::Strategy { strategy, file, message, .. } => write!(f, "Strategy '{}' failed on {}: {}", strategy, file, message),
+            AppError::Unavailable { strategy, file, message } => write!(f, "Strategy '{}' is temporarily unable to process {}: {}", strategy, file, message),
+            AppError::LimitExceeded { strategy, file, limit } => write!(f, "Strategy '{}' exceeded {} on {}", strategy, limit, file),
+            AppError::Timeout { operation, after } => write!(f, "{} timed out after {}s", operation, after.as_secs_f64()),
+            AppError::Network { operation, address, .. } => write!(f, "Failed to {} {}", operation, address),
+            AppError::InvalidRequest(msg) => write!(f, "Invalid request: {}", msg),
+            AppError::Config { message, .. } => write!(f, "Configuration error: {}", message),
             AppError::Processing(msg) => write!(f, "Processing error: {}", msg),
+        }?;
+        if f.alternate() {
+            let mut source = self.source();
+            while let Some(cause) = source {
+                write!(f, ": {}", cause)?;
+                source = cause.source();
+            }
         }
+        Ok(())
     }
 }
 
-impl Error for AppError {}
+impl Error for AppError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AppError::Io(err)
+            | AppError::File { source: err, .. }
+            | AppError::StorageFull { source: err, .. }
+            | AppError::Network { source: err, .. } => Some(err),
+            AppError::Watch(err) => Some(err),
+            AppError::Strategy { source, .. } | AppError::Config { source, .. } => {
+                source.as_deref().map(|source| source as &(dyn Error + 'static))
+            }
+            _ => None,
+        }
+    }
+}
 
 impl AppError {
     /// Create a new processing error with logging
@@ -45,9 +113,168 @@ impl AppError {
         log_error("Processing error", &msg);
         AppError::Processing(msg)
     }
-    
+
+    /// Error for a failed I/O `operation` on `path`, telling a missing file and a
+    /// full file system apart from other failures
+    pub fn file(operation: &'static str, path: &Path, source: io::Error) -> Self {
+        match source.kind() {
+            io::ErrorKind::NotFound => AppError::NotFound { path: path.to_path_buf() },
+            io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded => AppError::StorageFull { path: path.to_path_buf(), source },
+            _ => AppError::File { operation, path: path.to_path_buf(), source },
+        }
+    }
+
+    /// Configuration error without an underlying cause
+    pub fn config(message: impl Into<String>) -> Self {
+        AppError::Config { message: message.into(), source: None }
+    }
+
+    /// Stable identifier of the kind of error, for reports and webhooks
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Io(_) => "io",
+            AppError::Watch(_) => "watch",
+            AppError::File { .. } => "file_io",
+            AppError::NotFound { .. } => "not_found",
+            AppError::FileLocked { .. } => "file_locked",
+            AppError::StorageFull { .. } => "storage_full",
+            AppError::Rejected { .. } => "rejected",
+            AppError::Strategy { .. } => "strategy_failed",
+            AppError::Unavailable { .. } => "unavailable",
+            AppError::LimitExceeded { .. } => "limit_exceeded",
+            AppError::Timeout { .. } => "timeout",
+            AppError::Network { .. } => "network",
+            AppError::InvalidRequest(_) => "invalid_request",
+            AppError::Config { .. } => "config_invalid",
+            AppError::Processing(_) => "processing",
+        }
+    }
+
+    /// Whether the same operation may succeed when tried again later, e.g. once a
+    /// lock is released or disk space is freed
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AppError::FileLocked { .. } | AppError::StorageFull { .. } | AppError::Unavailable { .. } | AppError::Timeout { .. } => true,
+            AppError::Io(err) | AppError::File { source: err, .. } | AppError::Network { source: err, .. } => matches!(
+                err.kind(),
+                io::ErrorKind::Interrupted | io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock | io::ErrorKind::ResourceBusy
+            ),
+            _ => false,
+        }
+    }
+
+    /// The file the error is about, if it concerns a single file
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            AppError::File { path, .. }
+            | AppError::NotFound { path }
+            | AppError::FileLocked { path, .. }
+            | AppError::StorageFull { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
     /// Log this error with additional context
     pub fn log_with_context(&self, context: &str) {
         log_error(context, self);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn io_error(kind: io::ErrorKind) -> io::Error {
+        io::Error::new(kind, "test")
+    }
+
+    #[test]
+    fn file_errors_are_classified_by_kind() {
+        let path = Path::new("input/a.txt");
+        assert!(matches!(AppError::file("read", path, io_error(io::ErrorKind::NotFound)), AppError::NotFound { .. }));
+        assert!(matches!(AppError::file("write", path, io_error(io::ErrorKind::StorageFull)), AppError::StorageFull { .. }));
+        assert!(matches!(AppError::file("write", path, io_error(io::ErrorKind::QuotaExceeded)), AppError::StorageFull { .. }));
+        assert!(matches!(
+            AppError::file("read", path, io_error(io::ErrorKind::PermissionDenied)),
+            AppError::File { operation: "read", .. }
+        ));
+    }
+
+    #[test]
+    fn codes_are_stable() {
+        let path = PathBuf::from("input/a.txt");
+        let cases = [
+            (AppError::Io(io_error(io::ErrorKind::Other)), "io"),
+            (AppError::file("read", &path, io_error(io::ErrorKind::PermissionDenied)), "file_io"),
+            (AppError::NotFound { path: path.clone() }, "not_found"),
+            (AppError::FileLocked { path: path.clone(), attempts: 3 }, "file_locked"),
+            (AppError::file("write", &path, io_error(io::ErrorKind::StorageFull)), "storage_full"),
+            (AppError::Rejected { strategy: "script".into(), file: "a.txt".into(), reason: "no".into() }, "rejected"),
+            (AppError::Strategy { strategy: "command".into(), file: "a.txt".into(), message: "exit 1".into(), source: None }, "strategy_failed"),
+            (AppError::Unavailable { strategy: "command".into(), file: "a.txt".into(), message: "exit 75".into() }, "unavailable"),
+            (AppError::LimitExceeded { strategy: "script".into(), file: "a.txt".into(), limit: "x".into() }, "limit_exceeded"),
+            (AppError::Timeout { operation: "Command".into(), after: Duration::from_secs(1) }, "timeout"),
+            (AppError::Network { operation: "bind", address: "127.0.0.1:1".into(), source: io_error(io::ErrorKind::AddrInUse) }, "network"),
+            (AppError::InvalidRequest("bad".into()), "invalid_request"),
+            (AppError::config("bad"), "config_invalid"),
+            (AppError::Processing("other".into()), "processing"),
+        ];
+        for (error, code) in cases {
+            assert_eq!(error.code(), code, "{}", error);
+        }
+    }
+
+    #[test]
+    fn only_transient_failures_are_retryable() {
+        let path = PathBuf::from("input/a.txt");
+        assert!(AppError::FileLocked { path: path.clone(), attempts: 3 }.is_retryable());
+        assert!(AppError::file("write", &path, io_error(io::ErrorKind::StorageFull)).is_retryable());
+        assert!(AppError::Timeout { operation: "Command".into(), after: Duration::from_secs(1) }.is_retryable());
+        assert!(AppError::Unavailable { strategy: "command".into(), file: "a.txt".into(), message: "exit 75".into() }.is_retryable());
+        assert!(AppError::file("read", &path, io_error(io::ErrorKind::Interrupted)).is_retryable());
+        assert!(AppError::Io(io_error(io::ErrorKind::TimedOut)).is_retryable());
+
+        assert!(!AppError::NotFound { path: path.clone() }.is_retryable());
+        assert!(!AppError::file("read", &path, io_error(io::ErrorKind::PermissionDenied)).is_retryable());
+        assert!(!AppError::Rejected { strategy: "script".into(), file: "a.txt".into(), reason: "no".into() }.is_retryable());
+        assert!(!AppError::config("bad").is_retryable());
+        assert!(!AppError::Processing("other".into()).is_retryable());
+    }
+
+    #[test]
+    fn source_exposes_the_cause() {
+        let error = AppError::file("read", Path::new("a.txt"), io_error(io::ErrorKind::PermissionDenied));
+        let source = error.source().expect("file errors keep their cause");
+        assert_eq!(source.downcast_ref::<io::Error>().map(io::Error::kind), Some(io::ErrorKind::PermissionDenied));
+
+        let cause: BoxError = "compile error".into();
+        let error = AppError::Config { message: "bad script".into(), source: Some(cause) };
+        assert_eq!(error.source().map(ToString::to_string).as_deref(), Some("compile error"));
+
+        assert!(AppError::config("bad").source().is_none());
+        assert!(AppError::NotFound { path: PathBuf::from("a.txt") }.source().is_none());
+    }
+
+    #[test]
+    fn display_leaves_the_cause_to_the_alternate_form() {
+        let error = AppError::file("read", Path::new("a.txt"), io::Error::new(io::ErrorKind::PermissionDenied, "access denied"));
+        assert_eq!(error.to_string(), "Failed to read a.txt");
+        assert_eq!(format!("{:#}", error), "Failed to read a.txt: access denied");
+
+        let error = AppError::Network { operation: "bind", address: "127.0.0.1:1".into(), source: io_error(io::ErrorKind::AddrInUse) };
+        assert_eq!(format!("{:#}", error), "Failed to bind 127.0.0.1:1: test");
+
+        let cause: BoxError = "unexpected token".into();
+        let error = AppError::Config { message: "Invalid script s.rhai".into(), source: Some(cause) };
+        assert_eq!(format!("{:#}", error), "Configuration error: Invalid script s.rhai: unexpected token");
+        assert_eq!(format!("{:#}", AppError::config("bad")), "Configuration error: bad");
+    }
+
+    #[test]
+    fn path_is_only_set_for_file_errors() {
+        let path = Path::new("input/a.txt");
+        assert_eq!(AppError::NotFound { path: path.to_path_buf() }.path(), Some(path));
+        assert_eq!(AppError::file("read", path, io_error(io::ErrorKind::PermissionDenied)).path(), Some(path));
+        assert_eq!(AppError::config("bad").path(), None);
+    }
+}
@@ -0,0 +1,293 @@
+use crate::config::HealthConfig;
+use crate::queue::JobQueue;
+
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Name of the file written to check that the output directory is writable
+const PROBE_FILE_NAME: &str = ".cfp_health_probe";
+
+static WATCHER: Mutex<WatcherStatus> = Mutex::new(WatcherStatus { state: WatcherState::Starting, since: None, last_error: None });
+static LAST_SUCCESS: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// State of the file watcher, as reported by `supervise_watcher`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatcherState {
+    /// The service is still starting up
+    Starting,
+    Running,
+    /// The watcher failed and is being re-created
+    Restarting,
+    /// The watcher exited and will not be restarted
+    Stopped,
+}
+
+struct WatcherStatus {
+    state: WatcherState,
+    since: Option<Instant>,
+    last_error: Option<String>,
+}
+
+/// Record a change of the watcher state, with the error that caused a restart.
+/// Repeated failed restarts keep the time the watcher first failed.
+pub fn set_watcher_state(state: WatcherState, error: Option<String>) {
+    let mut watcher = WATCHER.lock().unwrap();
+    if watcher.state != state || watcher.since.is_none() {
+        watcher.state = state;
+        watcher.since = Some(Instant::now());
+    }
+    if error.is_some() {
+        watcher.last_error = error;
+    }
+}
+
+/// Record that a job finished successfully
+pub fn record_success() {
+    *LAST_SUCCESS.lock().unwrap() = Some(Instant::now());
+}
+
+/// Result of one health check
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthCheck {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+impl HealthCheck {
+    fn new(name: &'static str, ok: bool, detail: impl Into<String>) -> Self {
+        Self { name, ok, detail: detail.into() }
+    }
+}
+
+/// Outcome of a liveness or readiness probe
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    /// Whether every check passed
+    pub ok: bool,
+    pub watcher: WatcherState,
+    /// Seconds since a job last finished successfully, if one has
+    pub last_success_secs: Option<u64>,
+    pub checks: Vec<HealthCheck>,
+}
+
+impl HealthReport {
+    fn new(checks: Vec<HealthCheck>) -> Self {
+        Self {
+            ok: checks.iter().all(|check| check.ok),
+            watcher: WATCHER.lock().unwrap().state,
+            last_success_secs: LAST_SUCCESS.lock().unwrap().map(|at| at.elapsed().as_secs()),
+            checks,
+        }
+    }
+}
+
+impl std::fmt::Display for HealthReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", if self.ok { "OK" } else { "FAILING" })?;
+        for check in &self.checks {
+            write!(f, "\n  {} {}: {}", if check.ok { "ok  " } else { "FAIL" }, check.name, check.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether the service is alive: the watcher is running, or has not been
+/// restarting for longer than `watcher_grace_sec`
+pub fn liveness(config: &HealthConfig, queue: &JobQueue) -> HealthReport {
+    HealthReport::new(vec![watcher_check(config, queue)])
+}
+
+/// Whether the service can do its work: it is alive, both directories are
+/// usable, the queue is not saturated, intake is not paused and jobs are not stalled
+pub fn readiness(config: &HealthConfig, queue: &JobQueue, in_flight: usize, input_dir: &Path, output_dir: &Path) -> HealthReport {
+    HealthReport::new(vec![
+        watcher_check(config, queue),
+        input_dir_check(input_dir),
+        output_dir_check(output_dir),
+        queue_check(queue),
+        progress_check(config, queue, in_flight),
+    ])
+}
+
+fn watcher_check(config: &HealthConfig, queue: &JobQueue) -> HealthCheck {
+    evaluate_watcher(config, &WATCHER.lock().unwrap(), queue.is_closed(), Instant::now())
+}
+
+/// Judge the watcher as of `now`; a stopped watcher is only healthy once the queue is closed
+fn evaluate_watcher(config: &HealthConfig, watcher: &WatcherStatus, closed: bool, now: Instant) -> HealthCheck {
+    let elapsed = watcher.since.map(|since| now.saturating_duration_since(since)).unwrap_or_default();
+    let error = watcher.last_error.as_deref().unwrap_or("unknown error");
+    match watcher.state {
+        WatcherState::Starting => HealthCheck::new("watcher", true, "starting"),
+        WatcherState::Running => HealthCheck::new("watcher", true, format!("running for {}s", elapsed.as_secs())),
+        WatcherState::Restarting => HealthCheck::new(
+            "watcher",
+            elapsed <= Duration::from_secs(config.watcher_grace_sec),
+            format!("restarting for {}s after: {}", elapsed.as_secs(), error),
+        ),
+        // The watcher only stops for good when the service shuts down
+        WatcherState::Stopped => HealthCheck::new("watcher", closed, "stopped"),
+    }
+}
+
+/// The input directory must be listable and not read-only. No file is written
+/// there, since the watcher would pick it up.
+fn input_dir_check(input_dir: &Path) -> HealthCheck {
+    let result = std::fs::metadata(input_dir).and_then(|metadata| {
+        if metadata.permissions().readonly() {
+            return Err(std::io::Error::other("directory is read-only"));
+        }
+        std::fs::read_dir(input_dir).map(|_| ())
+    });
+    match result {
+        Ok(()) => HealthCheck::new("input_dir", true, format!("{} is accessible", input_dir.display())),
+        Err(e) => HealthCheck::new("input_dir", false, format!("{}: {}", input_dir.display(), e)),
+    }
+}
+
+/// The output directory must accept a new file
+fn output_dir_check(output_dir: &Path) -> HealthCheck {
+    let probe = output_dir.join(PROBE_FILE_NAME);
+    match std::fs::write(&probe, b"").and_then(|_| std::fs::remove_file(&probe)) {
+        Ok(()) => HealthCheck::new("output_dir", true, format!("{} is writable", output_dir.display())),
+        Err(e) => HealthCheck::new("output_dir", false, format!("{}: {}", output_dir.display(), e)),
+    }
+}
+
+fn queue_check(queue: &JobQueue) -> HealthCheck {
+    let stats = queue.stats();
+    if queue.is_closed() {
+        HealthCheck::new("queue", false, "shutting down")
+    } else if queue.is_paused() {
+        HealthCheck::new("queue", false, format!("paused with {} queued", stats.depth))
+    } else if queue.is_saturated() {
+        HealthCheck::new("queue", false, format!("saturated: {} queued, high-water mark {}", stats.depth, queue.high_water_mark()))
+    } else {
+        HealthCheck::new("queue", true, format!("{} queued", stats.depth))
+    }
+}
+
+/// Fails when files are waiting but no job has succeeded for `stalled_after_sec`
+fn progress_check(config: &HealthConfig, queue: &JobQueue, in_flight: usize) -> HealthCheck {
+    let last_success = *LAST_SUCCESS.lock().unwrap();
+    let watcher_since = WATCHER.lock().unwrap().since;
+    evaluate_progress(config, last_success, watcher_since, queue.depth(), in_flight, Instant::now())
+}
+
+/// Judge progress as of `now`, given the last success and when the watcher came up
+fn evaluate_progress(
+    config: &HealthConfig,
+    last_success: Option<Instant>,
+    watcher_since: Option<Instant>,
+    depth: usize,
+    in_flight: usize,
+    now: Instant,
+) -> HealthCheck {
+    let since_success = last_success.map(|at| now.saturating_duration_since(at));
+    let detail = match since_success {
+        Some(elapsed) => format!("last job succeeded {}s ago", elapsed.as_secs()),
+        None => "no job has succeeded yet".to_string(),
+    };
+    if config.stalled_after_sec == 0 || (depth == 0 && in_flight == 0) {
+        return HealthCheck::new("progress", true, detail);
+    }
+
+    let limit = Duration::from_secs(config.stalled_after_sec);
+    // Without a success yet, measure from when the watcher came up
+    let waited = since_success.or_else(|| watcher_since.map(|since| now.saturating_duration_since(since))).unwrap_or_default();
+    if waited > limit {
+        HealthCheck::new("progress", false, format!("stalled: {} with {} queued and {} in flight", detail, depth, in_flight))
+    } else {
+        HealthCheck::new("progress", true, detail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::QueueConfig;
+    use std::path::PathBuf;
+
+    fn config() -> HealthConfig {
+        HealthConfig { watcher_grace_sec: 30, stalled_after_sec: 60 }
+    }
+
+    fn restarting(since: Instant) -> WatcherStatus {
+        WatcherStatus { state: WatcherState::Restarting, since: Some(since), last_error: Some("inotify limit".to_string()) }
+    }
+
+    #[test]
+    fn restarting_watcher_fails_after_the_grace_period() {
+        let start = Instant::now();
+        let watcher = restarting(start);
+
+        assert!(evaluate_watcher(&config(), &watcher, false, start + Duration::from_secs(30)).ok);
+        let check = evaluate_watcher(&config(), &watcher, false, start + Duration::from_secs(31));
+        assert!(!check.ok);
+        assert_eq!(check.detail, "restarting for 31s after: inotify limit");
+
+        // A running watcher is healthy however long it has been up
+        let running = WatcherStatus { state: WatcherState::Running, ..watcher };
+        assert!(evaluate_watcher(&config(), &running, false, start + Duration::from_secs(3600)).ok);
+    }
+
+    #[test]
+    fn stopped_watcher_is_healthy_only_during_shutdown() {
+        let watcher = WatcherStatus { state: WatcherState::Stopped, since: Some(Instant::now()), last_error: None };
+        assert!(!evaluate_watcher(&config(), &watcher, false, Instant::now()).ok);
+        assert!(evaluate_watcher(&config(), &watcher, true, Instant::now()).ok);
+    }
+
+    #[test]
+    fn progress_stalls_only_while_work_is_waiting() {
+        let start = Instant::now();
+        let config = config();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Nothing waiting: healthy however long ago the last success was
+        assert!(evaluate_progress(&config, Some(start), Some(start), 0, 0, at(600)).ok);
+        // Work waiting: healthy until the limit passes since the last success
+        assert!(evaluate_progress(&config, Some(start), Some(start), 3, 1, at(60)).ok);
+        let check = evaluate_progress(&config, Some(start), Some(start), 3, 1, at(61));
+        assert!(!check.ok);
+        assert_eq!(check.detail, "stalled: last job succeeded 61s ago with 3 queued and 1 in flight");
+        // A new success makes it healthy again
+        assert!(evaluate_progress(&config, Some(at(61)), Some(start), 3, 1, at(62)).ok);
+
+        // Without a success yet, the limit counts from when the watcher came up
+        assert!(evaluate_progress(&config, None, Some(start), 0, 1, at(60)).ok);
+        assert!(!evaluate_progress(&config, None, Some(start), 0, 1, at(61)).ok);
+
+        // 0 disables the check
+        let disabled = HealthConfig { stalled_after_sec: 0, ..config };
+        assert!(evaluate_progress(&disabled, Some(start), Some(start), 3, 1, at(600)).ok);
+    }
+
+    #[tokio::test]
+    async fn saturated_or_paused_queue_is_not_ready() {
+        let queue_config = QueueConfig { capacity: 4, high_water_mark: 2, ..QueueConfig::default() };
+        let queue = JobQueue::new(&queue_config, Path::new("/in"));
+        assert!(queue_check(&queue).ok);
+
+        queue.push(PathBuf::from("/in/a"));
+        assert!(queue_check(&queue).ok);
+        queue.push(PathBuf::from("/in/b"));
+        let check = queue_check(&queue);
+        assert!(!check.ok);
+        assert_eq!(check.detail, "saturated: 2 queued, high-water mark 2");
+
+        // Draining below the high-water mark makes it ready again
+        queue.pop().await.unwrap();
+        assert!(queue_check(&queue).ok);
+
+        queue.pause();
+        assert!(!queue_check(&queue).ok);
+        queue.resume();
+        queue.close();
+        assert_eq!(queue_check(&queue).detail, "shutting down");
+    }
+}
@@ -0,0 +1,353 @@
+use crate::admin;
+use crate::config::{AppConfig, HttpConfig};
+use crate::error::AppError;
+use crate::health::{self, HealthReport};
+use crate::logging::{log_info, log_debug};
+use crate::metrics::{metrics, Gauges};
+use crate::processor::SharedProcessor;
+use crate::queue::JobQueue;
+
+use std::future::Future;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::time::{timeout, Duration};
+
+/// Largest accepted request line plus headers
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+/// Largest accepted request body
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+/// Time a client has to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A parsed HTTP request
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    /// Path without the query string
+    pub path: String,
+    /// Query string without the leading `?`
+    pub query: String,
+    /// Header names are lower case
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Request {
+    /// Value of the first header named `name` (lower case)
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Value of the first query parameter named `name`
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query.split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// An HTTP response; the connection is closed after it is sent
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn text(status: u16, body: impl Into<String>) -> Self {
+        Self { status, content_type: "text/plain; charset=utf-8", body: body.into() }
+    }
+
+    pub fn json(status: u16, value: &impl serde::Serialize) -> Self {
+        match serde_json::to_string_pretty(value) {
+            Ok(body) => Self { status, content_type: "application/json", body },
+            Err(e) => Self::text(500, format!("Failed to serialize response: {}", e)),
+        }
+    }
+
+    pub fn not_found() -> Self {
+        Self::text(404, "Not found\n")
+    }
+}
+
+/// Shared state the HTTP endpoints read from
+#[derive(Clone)]
+pub struct HttpState {
+    pub queue: Arc<JobQueue>,
+    pub processor: SharedProcessor,
+    /// Effective configuration, updated on every reload
+    pub config: watch::Receiver<AppConfig>,
+    /// Input directory the service was started with, for rescans
+    pub input_dir: PathBuf,
+    pub output_dir: PathBuf,
+    pub recursive: bool,
+}
+
+/// Bind the configured address, so a port conflict fails service startup
+pub async fn bind(config: &HttpConfig) -> Result<TcpListener, AppError> {
+    let listener = TcpListener::bind(&config.listen).await.map_err(|e| {
+        AppError::Network { operation: "bind HTTP listener on", address: config.listen.clone(), source: e }
+    })?;
+    log_info("HTTP endpoints listening", &format!("http://{}", listener.local_addr()?));
+    Ok(listener)
+}
+
+/// Serve the enabled endpoints on `listener` for as long as the service runs
+pub async fn run_http_server(listener: TcpListener, config: HttpConfig, state: HttpState) {
+    let config = Arc::new(config);
+    serve(listener, move |request| {
+        let config = Arc::clone(&config);
+        let state = state.clone();
+        async move { route(&config, &state, request).await }
+    }).await;
+}
+
+async fn route(config: &HttpConfig, state: &HttpState, request: Request) -> Response {
+    if config.admin && (request.path == "/admin" || request.path.starts_with("/admin/")) {
+        return admin::handle(config, state, &request).await;
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/metrics") if config.metrics => {
+            let gauges = Gauges {
+                queue: state.queue.stats(),
+                in_flight: state.processor.current().in_flight_count(),
+            };
+            Response {
+                status: 200,
+                content_type: "text/plain; version=0.0.4; charset=utf-8",
+                body: metrics().render(&gauges),
+            }
+        }
+        ("GET", "/health/live") if config.health => {
+            health_response(health::liveness(&state.config.borrow().health, &state.queue))
+        }
+        ("GET", "/health/ready") if config.health => {
+            let in_flight = state.processor.current().in_flight_count();
+            let health_config = state.config.borrow().health.clone();
+            health_response(health::readiness(&health_config, &state.queue, in_flight, &state.input_dir, &state.output_dir))
+        }
+        _ => Response::not_found(),
+    }
+}
+
+/// 200 for a passing probe and 503 for a failing one, with the report as body
+fn health_response(report: HealthReport) -> Response {
+    Response::json(if report.ok { 200 } else { 503 }, &report)
+}
+
+/// Send a GET request to a running service and return the status and body.
+/// An unspecified listen address such as `0.0.0.0` is reached over loopback.
+pub async fn get(listen: &str, path: &str) -> Result<(u16, String), AppError> {
+    let mut address: SocketAddr = listen.parse()
+        .map_err(|e| AppError::Config { message: format!("'{}' is not a valid address", listen), source: Some(Box::new(e)) })?;
+    if address.ip().is_unspecified() {
+        address.set_ip(if address.is_ipv4() { Ipv4Addr::LOCALHOST.into() } else { Ipv6Addr::LOCALHOST.into() });
+    }
+
+    let exchange = async {
+        let mut stream = TcpStream::connect(address).await?;
+        let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n", path, address);
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    };
+    let url = format!("http://{}{}", address, path);
+    let response = timeout(REQUEST_TIMEOUT, exchange).await
+        .map_err(|_| AppError::Timeout { operation: format!("Request to {}", url), after: REQUEST_TIMEOUT })?
+        .map_err(|e| AppError::Network { operation: "reach", address: url.clone(), source: e })?;
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let status = head.split_whitespace().nth(1).and_then(|status| status.parse().ok())
+        .ok_or_else(|| AppError::Network {
+            operation: "read the response of",
+            address: url.clone(),
+            source: std::io::Error::new(std::io::ErrorKind::InvalidData, "malformed status line"),
+        })?;
+    Ok((status, body.to_string()))
+}
+
+/// Accept connections and answer each request with `handler`
+pub async fn serve<H, F>(listener: TcpListener, handler: H)
+where
+    H: Fn(Request) -> F + Clone + Send + 'static,
+    F: Future<Output = Response> + Send,
+{
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                log_debug("Failed to accept HTTP connection", &e.to_string());
+                continue;
+            }
+        };
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, handler).await {
+                log_debug("HTTP request failed", &format!("{}: {}", peer, e));
+            }
+        });
+    }
+}
+
+async fn handle_connection<H, F>(mut stream: TcpStream, handler: H) -> Result<(), AppError>
+where
+    H: Fn(Request) -> F,
+    F: Future<Output = Response>,
+{
+    let response = match timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(request)) => handler(request).await,
+        Ok(Err(e)) => Response::text(400, format!("{:#}\n", e)),
+        Err(_) => Response::text(408, "Request timeout\n"),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason_phrase(response.status),
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request, AppError> {
+    let mut reader = BufReader::new(stream);
+    let mut header_bytes = 0;
+    let mut read_line = async |reader: &mut BufReader<&mut TcpStream>| -> Result<String, AppError> {
+        let mut line = String::new();
+        // Stop reading one byte past the limit, so a line without an end cannot grow unbounded
+        let remaining = (MAX_HEADER_BYTES - header_bytes + 1) as u64;
+        header_bytes += reader.take(remaining).read_line(&mut line).await?;
+        if header_bytes > MAX_HEADER_BYTES {
+            return Err(AppError::InvalidRequest("headers too large".to_string()));
+        }
+        Ok(line.trim_end().to_string())
+    };
+
+    let request_line = read_line(&mut reader).await?;
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(AppError::InvalidRequest("malformed request line".to_string()));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: method.to_string(),
+        path: path.to_string(),
+        query: query.to_string(),
+        headers: Vec::new(),
+        body: Vec::new(),
+    };
+
+    let mut content_length = 0;
+    loop {
+        let line = read_line(&mut reader).await?;
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let (name, value) = (name.trim().to_ascii_lowercase(), value.trim().to_string());
+            if name == "content-length" {
+                content_length = value.parse()
+                    .map_err(|_| AppError::InvalidRequest(format!("invalid Content-Length '{}'", value)))?;
+            }
+            request.headers.push((name, value));
+        }
+    }
+
+    if content_length > MAX_BODY_BYTES {
+        return Err(AppError::InvalidRequest("body too large".to_string()));
+    }
+    request.body.resize(content_length, 0);
+    reader.read_exact(&mut request.body).await?;
+    Ok(request)
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        // Fall back to the class of the status so the status line stays well-formed
+        _ => match status / 100 {
+            1 => "Informational",
+            2 => "Success",
+            3 => "Redirection",
+            4 => "Client Error",
+            _ => "Server Error",
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Send `raw` to a fresh connection and parse what the server side reads
+    async fn parse(raw: Vec<u8>) -> Result<Request, AppError> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+        // The client may be cut off once the server has seen enough
+        tokio::spawn(async move { client.write_all(&raw).await });
+        read_request(&mut server).await
+    }
+
+    #[tokio::test]
+    async fn parses_the_request_line_headers_and_body() {
+        let request = parse(b"POST /admin/jobs?limit=5 HTTP/1.1\r\nAuthorization: Bearer abc\r\nContent-Length: 4\r\n\r\nbody".to_vec()).await.unwrap();
+        assert_eq!((request.method.as_str(), request.path.as_str(), request.query.as_str()), ("POST", "/admin/jobs", "limit=5"));
+        assert_eq!(request.header("authorization"), Some("Bearer abc"));
+        assert_eq!(request.body, b"body");
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_and_oversized_requests() {
+        let invalid = |result: Result<Request, AppError>| matches!(result, Err(AppError::InvalidRequest(_)));
+        assert!(invalid(parse(b"GARBAGE\r\n\r\n".to_vec()).await));
+        assert!(invalid(parse(b"POST / HTTP/1.1\r\nContent-Length: many\r\n\r\n".to_vec()).await));
+        assert!(invalid(parse(format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", MAX_BODY_BYTES + 1).into_bytes()).await));
+
+        // A header line that never ends is rejected once it passes the limit
+        let mut endless = b"GET / HTTP/1.1\r\nX-Padding: ".to_vec();
+        endless.resize(MAX_HEADER_BYTES * 4, b'a');
+        assert!(invalid(parse(endless).await));
+    }
+
+    #[test]
+    fn every_status_has_a_reason_phrase() {
+        assert_eq!(reason_phrase(403), "Forbidden");
+        assert_eq!(reason_phrase(418), "Client Error");
+        assert_eq!(reason_phrase(599), "Server Error");
+        for status in 100..600 {
+            assert!(!reason_phrase(status).is_empty(), "no reason phrase for {}", status);
+        }
+    }
+
+    #[tokio::test]
+    async fn get_returns_status_and_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let listen = listener.local_addr().unwrap().to_string();
+        tokio::spawn(serve(listener, |request: Request| async move { Response::text(404, format!("no {}", request.path)) }));
+
+        assert_eq!(get(&listen, "/missing").await.unwrap(), (404, "no /missing".to_string()));
+        assert!(matches!(get("not an address", "/").await, Err(AppError::Config { .. })));
+    }
+}
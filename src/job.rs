@@ -0,0 +1,311 @@
+use crate::error::AppError;
+use crate::utils::unix_millis;
+
+use serde::{Serialize, Serializer};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+
+/// Number of failed jobs kept for inspection and retry
+const FAILED_JOB_HISTORY: usize = 100;
+
+/// Unique ID of one processing job, attached to every log line the job writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JobId(uuid::Uuid);
+
+impl JobId {
+    pub fn new() -> Self {
+        Self(uuid::Uuid::new_v4())
+    }
+}
+
+impl Default for JobId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for JobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for JobId {
+    type Err = uuid::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        uuid::Uuid::parse_str(s).map(Self)
+    }
+}
+
+impl Serialize for JobId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// The job a task is working on
+#[derive(Debug, Clone)]
+pub struct JobContext {
+    pub id: JobId,
+    /// Input file of the job
+    pub file: PathBuf,
+}
+
+impl JobContext {
+    pub fn new(file: &Path) -> Self {
+        Self { id: JobId::new(), file: file.to_path_buf() }
+    }
+}
+
+tokio::task_local! {
+    static CURRENT_JOB: JobContext;
+}
+
+/// Run `future` as part of `job`; log lines written while it runs carry the job's ID and file
+pub async fn in_job<F: Future>(job: JobContext, future: F) -> F::Output {
+    CURRENT_JOB.scope(job, future).await
+}
+
+/// Run blocking `f` as part of `job`, e.g. on a `spawn_blocking` thread, which
+/// does not inherit the job of the task that spawned it
+pub fn in_job_blocking<R>(job: Option<JobContext>, f: impl FnOnce() -> R) -> R {
+    match job {
+        Some(job) => CURRENT_JOB.sync_scope(job, f),
+        None => f(),
+    }
+}
+
+/// The job the current task is working on, if any
+pub fn current_job() -> Option<JobContext> {
+    CURRENT_JOB.try_with(JobContext::clone).ok()
+}
+
+/// A job that is currently being processed
+#[derive(Debug, Clone, Serialize)]
+pub struct RunningJob {
+    pub job_id: JobId,
+    pub file: PathBuf,
+    pub strategy: String,
+    /// Start time in milliseconds since the Unix epoch
+    pub started_at_ms: u64,
+    pub elapsed_ms: u64,
+    #[serde(skip)]
+    started: Instant,
+}
+
+/// A job that failed, kept so it can be inspected and retried
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedJob {
+    pub job_id: JobId,
+    pub file: PathBuf,
+    pub strategy: String,
+    pub error: String,
+    /// Stable identifier of the kind of error, see `AppError::code`
+    pub error_code: &'static str,
+    /// Whether processing the unchanged file again may succeed, see `AppError::is_retryable`
+    pub retryable: bool,
+    /// Failure time in milliseconds since the Unix epoch
+    pub failed_at_ms: u64,
+}
+
+/// When a file last failed with a non-retryable error, and its size then
+#[derive(Debug, Clone, Copy)]
+struct PermanentFailure {
+    failed_at_ms: u64,
+    size: Option<u64>,
+}
+
+impl PermanentFailure {
+    fn new(file: &Path, failed_at_ms: u64) -> Self {
+        Self { failed_at_ms, size: std::fs::metadata(file).map(|m| m.len()).ok() }
+    }
+}
+
+/// Running jobs and the most recently failed ones
+#[derive(Debug, Default)]
+pub struct JobTracker {
+    running: Mutex<HashMap<JobId, RunningJob>>,
+    /// Newest first, at most `FAILED_JOB_HISTORY` entries
+    failed: Mutex<VecDeque<FailedJob>>,
+    /// Files whose newest failure was not retryable. Not capped like `failed`, so
+    /// rescans keep skipping every such file until it changes or is processed.
+    permanent: Mutex<HashMap<PathBuf, PermanentFailure>>,
+}
+
+impl JobTracker {
+    pub fn start(&self, job_id: JobId, file: &Path, strategy: &str) {
+        self.running.lock().unwrap().insert(job_id, RunningJob {
+            job_id,
+            file: file.to_path_buf(),
+            strategy: strategy.to_string(),
+            started_at_ms: unix_millis(SystemTime::now()),
+            elapsed_ms: 0,
+            started: Instant::now(),
+        });
+    }
+
+    /// Record the end of a job. A success also clears earlier failures of the same file.
+    pub fn finish(&self, job_id: JobId, error: Option<&AppError>) {
+        let Some(job) = self.running.lock().unwrap().remove(&job_id) else {
+            return;
+        };
+        let mut failed = self.failed.lock().unwrap();
+        match error {
+            Some(error) => {
+                let failed_at_ms = unix_millis(SystemTime::now());
+                if error.is_retryable() {
+                    self.permanent.lock().unwrap().remove(&job.file);
+                } else {
+                    self.permanent.lock().unwrap().insert(job.file.clone(), PermanentFailure::new(&job.file, failed_at_ms));
+                }
+                failed.push_front(FailedJob {
+                    job_id,
+                    file: job.file,
+                    strategy: job.strategy,
+                    error: format!("{:#}", error),
+                    error_code: error.code(),
+                    retryable: error.is_retryable(),
+                    failed_at_ms,
+                });
+                failed.truncate(FAILED_JOB_HISTORY);
+            }
+            None => {
+                self.permanent.lock().unwrap().remove(&job.file);
+                failed.retain(|failure| failure.file != job.file);
+            }
+        }
+    }
+
+    /// Running jobs, longest running first
+    pub fn running(&self) -> Vec<RunningJob> {
+        let mut running: Vec<RunningJob> = self.running.lock().unwrap().values().cloned().collect();
+        for job in &mut running {
+            job.elapsed_ms = job.started.elapsed().as_millis() as u64;
+        }
+        running.sort_by_key(|job| job.started);
+        running
+    }
+
+    /// Recently failed jobs, newest first
+    pub fn failed(&self) -> Vec<FailedJob> {
+        self.failed.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn find_failed(&self, job_id: JobId) -> Option<FailedJob> {
+        self.failed.lock().unwrap().iter().find(|job| job.job_id == job_id).cloned()
+    }
+
+    /// Forget a failure, so its file is processed again even if unchanged
+    pub fn remove_failed(&self, job_id: JobId) {
+        let mut failed = self.failed.lock().unwrap();
+        if let Some(job) = failed.iter().find(|job| job.job_id == job_id) {
+            self.permanent.lock().unwrap().remove(&job.file);
+        }
+        failed.retain(|job| job.job_id != job_id);
+    }
+
+    /// Put back a failure taken out with `remove_failed`
+    pub fn restore_failed(&self, job: FailedJob) {
+        if !job.retryable {
+            self.permanent.lock().unwrap().insert(job.file.clone(), PermanentFailure::new(&job.file, job.failed_at_ms));
+        }
+        self.failed.lock().unwrap().push_front(job);
+    }
+
+    /// Whether the newest failure of `file` was not retryable and the file has kept its
+    /// size and not been modified since, i.e. processing it again would fail the same way.
+    /// A file that has changed is forgotten, so it is tried again.
+    pub fn failed_permanently_since(&self, file: &Path, modified_ms: u64, size: u64) -> bool {
+        let mut permanent = self.permanent.lock().unwrap();
+        let Some(failure) = permanent.get(file) else {
+            return false;
+        };
+        let unchanged = failure.failed_at_ms >= modified_ms && failure.size.is_none_or(|failed_size| failed_size == size);
+        if !unchanged {
+            permanent.remove(file);
+        }
+        unchanged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    fn fail(tracker: &JobTracker, file: &Path, error: AppError) -> JobId {
+        let job_id = JobId::new();
+        tracker.start(job_id, file, "hash");
+        tracker.finish(job_id, Some(&error));
+        job_id
+    }
+
+    #[test]
+    fn only_non_retryable_failures_after_the_last_change_are_permanent() {
+        let tracker = JobTracker::default();
+        let file = Path::new("/in/a.txt");
+        let before = unix_millis(SystemTime::now());
+        let job_id = fail(&tracker, file, AppError::Rejected { strategy: "script".into(), file: "a.txt".into(), reason: "bad".into() });
+
+        assert!(tracker.failed_permanently_since(file, before, 0));
+        assert!(!tracker.failed_permanently_since(Path::new("/in/b.txt"), before, 0));
+
+        let job = tracker.find_failed(job_id).unwrap();
+        tracker.remove_failed(job_id);
+        assert!(!tracker.failed_permanently_since(file, before, 0));
+        tracker.restore_failed(job);
+        assert!(tracker.failed_permanently_since(file, before, 0));
+
+        fail(&tracker, file, AppError::StorageFull { path: file.to_path_buf(), source: io::Error::other("disk full") });
+        assert!(!tracker.failed_permanently_since(file, before, 0), "the newest failure decides");
+    }
+
+    #[test]
+    fn permanent_failures_outlive_the_history_until_the_file_changes() {
+        let dir = std::env::temp_dir().join(format!("cfp-jobs-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tracker = JobTracker::default();
+        let before = unix_millis(SystemTime::now());
+        let files: Vec<PathBuf> = (0..FAILED_JOB_HISTORY + 20).map(|i| dir.join(format!("{i}.txt"))).collect();
+        for file in &files {
+            std::fs::write(file, b"bad").unwrap();
+            fail(&tracker, file, AppError::Rejected { strategy: "script".into(), file: file.display().to_string(), reason: "bad".into() });
+        }
+
+        assert_eq!(tracker.failed().len(), FAILED_JOB_HISTORY);
+        assert!(files.iter().all(|file| tracker.failed_permanently_since(file, before, 3)), "failures dropped from the history are still skipped");
+
+        // A change of modification time or size is retried, and forgotten from then on
+        assert!(!tracker.failed_permanently_since(&files[0], u64::MAX, 3));
+        assert!(!tracker.failed_permanently_since(&files[0], before, 3));
+        assert!(!tracker.failed_permanently_since(&files[1], before, 4));
+
+        // So is a file processed successfully since
+        let job_id = JobId::new();
+        tracker.start(job_id, &files[2], "hash");
+        tracker.finish(job_id, None);
+        assert!(!tracker.failed_permanently_since(&files[2], before, 3));
+        assert!(tracker.failed_permanently_since(&files[3], before, 3));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn success_clears_earlier_failures() {
+        let tracker = JobTracker::default();
+        let file = Path::new("/in/a.txt");
+        fail(&tracker, file, AppError::NotFound { path: file.to_path_buf() });
+        assert_eq!(tracker.failed().len(), 1);
+
+        let job_id = JobId::new();
+        tracker.start(job_id, file, "hash");
+        tracker.finish(job_id, None);
+        assert!(tracker.failed().is_empty());
+        assert!(tracker.running().is_empty());
+    }
+}
@@ -0,0 +1,27 @@
+//! Concurrent File Processor library
+//!
+//! Exposes the building blocks used by the service binary so that custom
+//! processing strategies (see `examples/`) can be built against them.
+
+pub mod error;
+pub mod watcher;
+pub mod processor;
+pub mod logging;
+pub mod utils;
+pub mod config;
+pub mod validation;
+pub mod trigger;
+pub mod queue;
+pub mod service;
+pub mod dry_run;
+pub mod reload;
+pub mod job;
+pub mod metrics;
+pub mod http;
+pub mod admin;
+pub mod health;
+pub mod results;
+pub mod webhook;
+pub mod command;
+pub mod wasm;
+pub mod script;
@@ -1,49 +1,240 @@
-use flexi_logger::{Duplicate, FileSpec, FlexiLoggerError, Logger};
-use log::{info, error, warn, debug};
-use crate::config::LoggingConfig;
+use flexi_logger::{style, AdaptiveFormat, Age, Cleanup, Criterion, DeferredNow, Duplicate, FileSpec, FlexiLoggerError, Logger, LoggerHandle, Naming, Record};
+use log::kv::{self, Key, Source, ToValue, Value, VisitSource};
+use log::{info, Level};
+use crate::config::{LogFormat, LoggingConfig, RotationAge, StdoutLevel};
+use crate::job::{current_job, JobId};
 
-pub fn init_logging(config: &LoggingConfig) -> Result<(), FlexiLoggerError> {
+use std::fmt;
+use std::io::{self, Write};
+
+/// Structured field attached to a log line, e.g. `("file", Value::from_display(&path.display()))`
+pub type LogField<'a> = (&'a str, Value<'a>);
+
+/// Key under which the `log_*` helpers attach their context
+const CONTEXT_KEY: &str = "context";
+
+/// Key under which lines written by a job carry its ID
+const JOB_ID_KEY: &str = "job_id";
+
+/// Start the logger. The returned handle can change the log level at runtime.
+pub fn init_logging(config: &LoggingConfig) -> Result<LoggerHandle, FlexiLoggerError> {
     let mut logger = Logger::try_with_str(&config.level)?
         .log_to_file(FileSpec::default()
             .directory(&config.log_dir)
             .basename(&config.log_basename));
-    
+
+    logger = match config.format {
+        LogFormat::Text => logger
+            .format_for_files(text_format)
+            .adaptive_format_for_stdout(AdaptiveFormat::Custom(text_format, colored_text_format)),
+        LogFormat::Json => logger.format(json_format),
+    };
+
+    if let Some(criterion) = rotation_criterion(config) {
+        logger = logger.rotate(criterion, Naming::Timestamps, rotation_cleanup(config));
+    }
+
     if config.duplicate_to_stdout {
-        logger = logger.duplicate_to_stdout(Duplicate::Info);
+        logger = logger.duplicate_to_stdout(match config.stdout_level {
+            StdoutLevel::Error => Duplicate::Error,
+            StdoutLevel::Warn => Duplicate::Warn,
+            StdoutLevel::Info => Duplicate::Info,
+            StdoutLevel::Debug => Duplicate::Debug,
+            StdoutLevel::Trace => Duplicate::Trace,
+            StdoutLevel::All => Duplicate::All,
+        });
     }
-    
-    logger.start()?;
-    
+
+    let handle = logger.start()?;
+
     info!("Logging system initialized successfully");
-    Ok(())
+    Ok(handle)
+}
+
+/// When to start a new log file, or `None` to keep one file per run
+fn rotation_criterion(config: &LoggingConfig) -> Option<Criterion> {
+    let size = (config.rotate_size_mb > 0).then(|| config.rotate_size_mb * 1024 * 1024);
+    let age = match config.rotate_age {
+        RotationAge::Never => None,
+        RotationAge::Hour => Some(Age::Hour),
+        RotationAge::Day => Some(Age::Day),
+    };
+    match (age, size) {
+        (Some(age), Some(size)) => Some(Criterion::AgeOrSize(age, size)),
+        (Some(age), None) => Some(Criterion::Age(age)),
+        (None, Some(size)) => Some(Criterion::Size(size)),
+        (None, None) => None,
+    }
+}
+
+/// Which rotated log files to keep and whether to compress them
+fn rotation_cleanup(config: &LoggingConfig) -> Cleanup {
+    let keep = if config.keep_files == 0 { usize::MAX } else { config.keep_files };
+    match (config.compress, config.keep_files) {
+        (true, _) => Cleanup::KeepCompressedFiles(keep),
+        (false, 0) => Cleanup::Never,
+        (false, _) => Cleanup::KeepLogFiles(keep),
+    }
 }
 
 /// Log an error with additional context
 pub fn log_error<E: std::fmt::Display>(context: &str, error: &E) {
-    error!("{} | {}", context, error);
+    emit(Level::Error, Some(context), format_args!("{:#}", error), &[]);
 }
 
 /// Log a warning with additional context
 pub fn log_warning(context: &str, message: &str) {
-    warn!("{} | {}", context, message);
+    emit(Level::Warn, Some(context), format_args!("{}", message), &[]);
 }
 
 /// Log debug information
 pub fn log_debug(context: &str, message: &str) {
-    debug!("{} | {}", context, message);
+    emit(Level::Debug, Some(context), format_args!("{}", message), &[]);
 }
 
 /// Log info message
 pub fn log_info(context: &str, message: &str) {
-    info!("{} | {}", context, message);
+    emit(Level::Info, Some(context), format_args!("{}", message), &[]);
 }
 
 /// Log info message without context (for simple messages)
 pub fn log_info_simple(message: &str) {
-    info!("{}", message);
+    emit(Level::Info, None, format_args!("{}", message), &[]);
 }
 
 /// Log error message without context (for simple error messages)
 pub fn log_error_simple(message: &str) {
-    error!("{}", message);
+    emit(Level::Error, None, format_args!("{}", message), &[]);
+}
+
+/// Log a message with context and structured fields. Fields are written as
+/// separate keys in the JSON format; the text format only shows the message.
+pub fn log_fields(level: Level, context: &str, message: &str, fields: &[LogField]) {
+    emit(level, Some(context), format_args!("{}", message), fields);
+}
+
+/// Context, job and fields of a log line, passed to the formatters as key-values
+struct LineFields<'a> {
+    context: Option<&'a str>,
+    /// ID and input file of the job writing the line
+    job: Option<(JobId, String)>,
+    fields: &'a [LogField<'a>],
+}
+
+impl Source for LineFields<'_> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+        if let Some(context) = self.context {
+            visitor.visit_pair(Key::from_str(CONTEXT_KEY), Value::from(context))?;
+        }
+        if let Some((job_id, file)) = &self.job {
+            visitor.visit_pair(Key::from_str(JOB_ID_KEY), Value::from_display(job_id))?;
+            if !self.fields.iter().any(|(key, _)| *key == "file") {
+                visitor.visit_pair(Key::from_str("file"), Value::from(file.as_str()))?;
+            }
+        }
+        for (key, value) in self.fields {
+            visitor.visit_pair(Key::from_str(key), value.to_value())?;
+        }
+        Ok(())
+    }
+}
+
+fn emit(level: Level, context: Option<&str>, message: fmt::Arguments, fields: &[LogField]) {
+    if level > log::max_level() {
+        return;
+    }
+    let job = current_job().map(|job| (job.id, job.file.display().to_string()));
+    let line_fields = LineFields { context, job, fields };
+    log::logger().log(&Record::builder()
+        .level(level)
+        .target(module_path!())
+        .module_path_static(Some(module_path!()))
+        .file_static(Some(file!()))
+        .line(Some(line!()))
+        .args(message)
+        .key_values(&line_fields)
+        .build());
+}
+
+/// `LEVEL [module] [job <id>] context | message`
+fn text_format(w: &mut dyn Write, _now: &mut DeferredNow, record: &Record) -> io::Result<()> {
+    write!(w, "{} [{}] {}", record.level(), record.module_path().unwrap_or("<unnamed>"), TextMessage(record))
+}
+
+/// Text format with the level and message colored by level, for terminals
+fn colored_text_format(w: &mut dyn Write, _now: &mut DeferredNow, record: &Record) -> io::Result<()> {
+    let level = record.level();
+    write!(
+        w,
+        "{} [{}] {}",
+        style(level).paint(level.to_string()),
+        record.module_path().unwrap_or("<unnamed>"),
+        style(level).paint(TextMessage(record).to_string())
+    )
+}
+
+/// Message of a record, prefixed with its job ID and context if it has them
+struct TextMessage<'a, 'r>(&'a Record<'r>);
+
+impl fmt::Display for TextMessage<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(job_id) = self.0.key_values().get(Key::from_str(JOB_ID_KEY)) {
+            write!(f, "[job {}] ", job_id)?;
+        }
+        if let Some(context) = self.0.key_values().get(Key::from_str(CONTEXT_KEY)) {
+            write!(f, "{} | ", context)?;
+        }
+        write!(f, "{}", self.0.args())
+    }
+}
+
+/// One JSON object per line: timestamp, level, target, context, message, the
+/// job ID and file for lines written by a job, and any structured fields of the record
+fn json_format(w: &mut dyn Write, now: &mut DeferredNow, record: &Record) -> io::Result<()> {
+    let mut fields = JsonFields(Vec::new());
+    // Collecting into a Vec cannot fail
+    let _ = record.key_values().visit(&mut fields);
+
+    let mut line = vec![
+        ("timestamp".to_string(), serde_json::Value::from(now.format_rfc3339())),
+        ("level".to_string(), serde_json::Value::from(record.level().as_str())),
+        ("target".to_string(), serde_json::Value::from(record.target())),
+    ];
+    if let Some(index) = fields.0.iter().position(|(key, _)| key == CONTEXT_KEY) {
+        line.push(fields.0.remove(index));
+    }
+    line.push(("message".to_string(), serde_json::Value::from(record.args().to_string())));
+    line.extend(fields.0);
+
+    write!(w, "{{")?;
+    for (index, (key, value)) in line.iter().enumerate() {
+        if index > 0 {
+            write!(w, ",")?;
+        }
+        serde_json::to_writer(&mut *w, key)?;
+        write!(w, ":")?;
+        serde_json::to_writer(&mut *w, value)?;
+    }
+    write!(w, "}}")
+}
+
+/// Collects the key-values of a record as JSON values, keeping numbers and booleans typed
+struct JsonFields(Vec<(String, serde_json::Value)>);
+
+impl<'kvs> VisitSource<'kvs> for JsonFields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let json = if let Some(flag) = value.to_bool() {
+            serde_json::Value::from(flag)
+        } else if let Some(number) = value.to_u64() {
+            serde_json::Value::from(number)
+        } else if let Some(number) = value.to_i64() {
+            serde_json::Value::from(number)
+        } else if let Some(number) = value.to_f64() {
+            serde_json::Value::from(number)
+        } else {
+            serde_json::Value::from(value.to_string())
+        };
+        self.0.push((key.to_string(), json));
+        Ok(())
+    }
 }
@@ -1,71 +1,216 @@
 use std::path::PathBuf;
-use tokio::sync::mpsc;
-use crate::logging::{log_info, log_error, log_info_simple, init_logging};
-use crate::utils::{setup_directories};
-use crate::processor::{FileProcessor, log_processing_result};
-use crate::config::AppConfig;
-
-mod error;
-mod watcher;
-mod processor;
-mod logging;
-mod utils;
-mod config;
-
-use watcher::watch_files;
-
-/// starts a file watcher, and processes new files via an MPSC channel.
+use std::process::ExitCode;
+use std::time::Duration;
+use clap::Parser;
+use concurrent_file_processor::logging::{init_logging, log_warning};
+use concurrent_file_processor::utils::{ensure_output_directory, scan_input_directory, setup_directories, validate_file_path};
+use concurrent_file_processor::processor::FileProcessor;
+use concurrent_file_processor::config::{AppConfig, LoadedConfig, TriggerConfig, CONFIG_FILE_NAME, config_search_paths, discover_config_file, write_config_template};
+use concurrent_file_processor::dry_run::{plan_files, render_table};
+use concurrent_file_processor::validation::ConfigIssue;
+use concurrent_file_processor::error::AppError;
+use concurrent_file_processor::service::run_service;
+use concurrent_file_processor::http;
+use concurrent_file_processor::webhook::wait_for_deliveries;
+use concurrent_file_processor::reload::ConfigReloader;
+use flexi_logger::LoggerHandle;
+
+mod cli;
+
+use cli::{Cli, Command, ConfigCommand, PlanFormat};
+
+/// Parses the command line and runs the selected command.
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Load configuration
-    let config = AppConfig::load_or_default().map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
-    
-    // Initialize logging with configuration
-    init_logging(&config.logging).map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
-    
-    log_info_simple("Starting Concurrent File Processor service...");
-    
-    let input_dir = config.input_dir();
-    let output_dir = config.output_dir();
-
-    setup_directories(&input_dir, &output_dir).await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
-    
-    // Create a Multi-Producer, Single-Consumer (MPSC) channel.
-    let (tx, mut rx) = mpsc::channel::<PathBuf>(config.watcher.channel_buffer_size);
-
-    // Create file processor with configuration
-    let processor = FileProcessor::new(config.processing.clone());
-    processor.process_initial_files(&input_dir, &output_dir).await.map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?;
-
-    let watcher_input_dir = input_dir.clone();
-    let watcher_tx = tx.clone(); // Clone the sender for the watcher task.
-    let watcher_config = config.watcher.clone();
-    tokio::spawn(async move {
-        log_info_simple("Starting file watcher...");
-        if let Err(e) = watch_files(&watcher_input_dir, watcher_tx, &watcher_config).await {
-            log_error("File watcher experienced an error", &e);
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(&cli).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            ExitCode::FAILURE
         }
-    });
-
-    // --- File Processor Task ---
-    log_info_simple("Starting main file processing loop...");
-    while let Some(file_path) = rx.recv().await {
-        log_info("Received new file for processing", &format!("{}", file_path.display()));
-        let output_dir_clone = output_dir.clone(); // Clone for each spawned task.
-        let processor_clone = processor.clone(); // Clone processor for each task
-
-        tokio::spawn(async move {
-            match processor_clone.process_file(&file_path, &output_dir_clone).await {
-                Ok(result) => {
-                    log_processing_result("File processing", &result);
-                }
-                Err(e) => {
-                    log_error("Failed to process new file", &format!("{}: {}", file_path.display(), e));
-                }
+    }
+}
+
+async fn run(cli: &Cli) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    if cli.dry_run {
+        if let Some(Command::Config { .. } | Command::Health { .. }) = cli.command {
+            return Err("--dry-run cannot be used with config or health commands".into());
+        }
+        print_dry_run_plan(cli).await?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let report = match cli.command.clone().unwrap_or(Command::Run) {
+        Command::Run => {
+            let (loaded, logger) = start(cli)?;
+            let reloader = match loaded.path {
+                Some(path) if loaded.config.reload.enabled => Some(ConfigReloader::new(path, cli.overrides(), cli.strict_config, logger)),
+                _ => None,
+            };
+            run_service(&loaded.config, reloader).await?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Command::Once => {
+            let (LoadedConfig { config, .. }, _logger) = start(cli)?;
+            let input_dir = config.input_dir();
+            let output_dir = config.output_dir();
+            setup_directories(&input_dir, &output_dir).await?;
+
+            let processor = FileProcessor::new(config.processing.clone())?
+                .with_trigger(config.trigger.clone())
+                .with_webhooks(&config.webhook)?;
+            let report = processor.process_initial_files(&input_dir, &output_dir, config.watcher.recursive, config.queue.workers).await?;
+            wait_for_deliveries(Duration::from_secs(config.webhook.shutdown_timeout_sec)).await;
+            report
+        }
+        Command::Process { files } => {
+            let (LoadedConfig { config, .. }, _logger) = start(cli)?;
+            for file in &files {
+                validate_file_path(file)?;
             }
-        });
+            let output_dir = config.output_dir();
+            ensure_output_directory(&output_dir).await?;
+
+            // Explicitly requested files are processed right away, regardless of the trigger mode
+            let processor = FileProcessor::new(config.processing.clone())?.with_webhooks(&config.webhook)?;
+            let report = processor.process_paths(files, &output_dir, "Requested file processing", config.queue.workers).await;
+            wait_for_deliveries(Duration::from_secs(config.webhook.shutdown_timeout_sec)).await;
+            report
+        }
+        Command::Health { live } => return check_health(cli, live).await,
+        Command::Config { action } => {
+            run_config_command(cli, action)?;
+            return Ok(ExitCode::SUCCESS);
+        }
+    };
+
+    println!("{}", report);
+    Ok(if report.is_success() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
+
+/// Query the health endpoint of the running service and print its report
+async fn check_health(cli: &Cli, live: bool) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    let config = load_config(cli)?;
+    if !config.http.enabled || !config.http.health {
+        return Err("Health probes are served over HTTP; set http.enabled and http.health to true".into());
     }
+    let path = if live { "/health/live" } else { "/health/ready" };
+    let (status, body) = http::get(&config.http.listen, path).await?;
+    println!("{}", body);
+    Ok(if status == 200 { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
+
+/// Print what the selected command would do, without writing or removing any file
+async fn print_dry_run_plan(cli: &Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config(cli)?;
+    for warning in check_config(&config)? {
+        eprintln!("Warning: {}", warning);
+    }
+
+    let (files, trigger) = match cli.command.clone() {
+        // Explicitly requested files are processed right away, regardless of the trigger mode
+        Some(Command::Process { files }) => (files, TriggerConfig::default()),
+        _ => (scan_input_directory(&config.input_dir(), config.watcher.recursive).await?, config.trigger.clone()),
+    };
 
-    log_info_simple("Concurrent File Processor service stopped gracefully.");
+    let plan = plan_files(files, &config, &trigger);
+    match cli.format {
+        PlanFormat::Table => println!("{}", render_table(&plan)),
+        PlanFormat::Json => println!("{}", serde_json::to_string_pretty(&plan)?),
+    }
+    Ok(())
+}
+
+/// Load and validate the configuration and initialize logging for the processing commands
+fn start(cli: &Cli) -> Result<(LoadedConfig, LoggerHandle), Box<dyn std::error::Error>> {
+    let loaded = load_layered_config(cli)?;
+    let issues = check_config(&loaded.config)?;
+    let logger = init_logging(&loaded.config.logging)?;
+    for warning in &loaded.warnings {
+        log_warning("Configuration warning", warning);
+    }
+    for issue in &issues {
+        log_warning("Configuration warning", &format!("{}: {}", issue.field, issue.message));
+    }
+    Ok((loaded, logger))
+}
+
+/// Validate the configuration, failing on errors and returning the warnings
+fn check_config(config: &AppConfig) -> Result<Vec<ConfigIssue>, Box<dyn std::error::Error>> {
+    let (errors, warnings): (Vec<_>, Vec<_>) = config.validate().into_iter().partition(ConfigIssue::is_error);
+    if !errors.is_empty() {
+        let details = errors.iter().map(|issue| format!("  {}", issue)).collect::<Vec<_>>().join("\n");
+        return Err(format!("Invalid configuration:\n{}", details).into());
+    }
+    Ok(warnings)
+}
+
+/// Load the configuration for commands that do not log, printing warnings to stderr
+fn load_config(cli: &Cli) -> Result<AppConfig, AppError> {
+    let loaded = load_layered_config(cli)?;
+    for warning in &loaded.warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    Ok(loaded.config)
+}
+
+/// Load the configuration from `--config` (or the default location), layered with
+/// `CFP_*` environment variables and command-line overrides
+fn load_layered_config(cli: &Cli) -> Result<LoadedConfig, AppError> {
+    let loaded = AppConfig::load_layered(cli.config.as_deref(), &cli.overrides())?;
+    if cli.strict_config {
+        loaded.deny_warnings()?;
+    }
+    Ok(loaded)
+}
+
+fn run_config_command(cli: &Cli, action: ConfigCommand) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        ConfigCommand::Init { force } => {
+            let path = cli.config.clone().unwrap_or_else(|| PathBuf::from(CONFIG_FILE_NAME));
+            if path.exists() && !force {
+                return Err(format!("{} already exists (use --force to overwrite)", path.display()).into());
+            }
+            write_config_template(&path)?;
+            println!("Wrote default configuration to {}", path.display());
+        }
+        ConfigCommand::Validate => {
+            let config = load_config(cli)?;
+            let issues = config.validate();
+            for issue in &issues {
+                println!("{}", issue);
+            }
+            if issues.iter().any(ConfigIssue::is_error) {
+                return Err("Configuration is invalid".into());
+            }
+            println!("Configuration is valid");
+        }
+        ConfigCommand::Path => {
+            let in_use = cli.config.clone().or_else(discover_config_file);
+            if cli.config.is_none() {
+                for path in config_search_paths() {
+                    let marker = if Some(&path) == in_use.as_ref() { "*" } else { " " };
+                    println!("{} {}", marker, path.display());
+                }
+            }
+            match in_use {
+                Some(path) => println!("Using {}", path.display()),
+                None => println!("No configuration file found, using built-in defaults"),
+            }
+        }
+        ConfigCommand::Show { sources } => {
+            let loaded = load_layered_config(cli)?;
+            for warning in &loaded.warnings {
+                eprintln!("Warning: {}", warning);
+            }
+            if sources {
+                println!("{}", loaded.describe_sources()?);
+            } else {
+                print!("{}", toml::to_string_pretty(&loaded.config)?);
+            }
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,277 @@
+use crate::processor::ProcessingResult;
+use crate::queue::QueueStats;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Upper bounds in seconds of the processing time histogram buckets
+const DURATION_BUCKETS: [f64; 12] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+static METRICS: Metrics = Metrics::new();
+
+/// Process-wide metrics registry
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// How a file came to be queued
+#[derive(Debug, Clone, Copy)]
+pub enum DetectionSource {
+    /// A notify event
+    Watcher,
+    /// The startup scan or a rescan
+    Scan,
+}
+
+/// Counters collected while the service runs, rendered in the Prometheus text format.
+/// Gauges such as queue depth are read from their owners when rendering.
+pub struct Metrics {
+    detected_by_watcher: AtomicU64,
+    detected_by_scan: AtomicU64,
+    file_retries: AtomicU64,
+    job_retries: AtomicU64,
+    watcher_errors: AtomicU64,
+    strategies: Mutex<BTreeMap<String, StrategyMetrics>>,
+}
+
+/// Counters of the files processed by one strategy
+#[derive(Default)]
+struct StrategyMetrics {
+    processed: u64,
+    failed: u64,
+    bytes_in: u64,
+    bytes_out: u64,
+    duration: Histogram,
+}
+
+/// Reads one counter of `StrategyMetrics`
+type StrategyCounter = fn(&StrategyMetrics) -> u64;
+
+/// Cumulative histogram with the bounds in `DURATION_BUCKETS`
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; DURATION_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(DURATION_BUCKETS) {
+            if value <= bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// Current values of the gauges owned by other components
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gauges {
+    pub queue: QueueStats,
+    pub in_flight: usize,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            detected_by_watcher: AtomicU64::new(0),
+            detected_by_scan: AtomicU64::new(0),
+            file_retries: AtomicU64::new(0),
+            job_retries: AtomicU64::new(0),
+            watcher_errors: AtomicU64::new(0),
+            strategies: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn record_detected(&self, source: DetectionSource, count: u64) {
+        let counter = match source {
+            DetectionSource::Watcher => &self.detected_by_watcher,
+            DetectionSource::Scan => &self.detected_by_scan,
+        };
+        counter.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn record_processed(&self, result: &ProcessingResult) {
+        let mut strategies = self.strategies.lock().unwrap();
+        let strategy = strategies.entry(result.strategy.clone()).or_default();
+        strategy.processed += 1;
+        strategy.bytes_in += result.original_size as u64;
+        strategy.bytes_out += result.processed_size as u64;
+        strategy.duration.observe(result.processing_time_ms as f64 / 1000.0);
+    }
+
+    pub fn record_failed(&self, strategy: &str) {
+        let mut strategies = self.strategies.lock().unwrap();
+        strategies.entry(strategy.to_string()).or_default().failed += 1;
+    }
+
+    /// Record a retry while waiting for a locked input file
+    pub fn record_file_retry(&self) {
+        self.file_retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a job retried after failing with a retryable error
+    pub fn record_job_retry(&self) {
+        self.job_retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a watcher failure that required a restart
+    pub fn record_watcher_error(&self) {
+        self.watcher_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Render all metrics in the Prometheus text exposition format
+    pub fn render(&self, gauges: &Gauges) -> String {
+        let mut out = String::new();
+
+        header(&mut out, "cfp_files_detected_total", "counter", "Files queued for processing, by how they were found");
+        sample(&mut out, "cfp_files_detected_total", &[("source", "watcher")], self.detected_by_watcher.load(Ordering::Relaxed));
+        sample(&mut out, "cfp_files_detected_total", &[("source", "scan")], self.detected_by_scan.load(Ordering::Relaxed));
+
+        {
+            let strategies = self.strategies.lock().unwrap();
+            let per_strategy: [(&str, &str, StrategyCounter); 4] = [
+                ("cfp_files_processed_total", "Files processed successfully", |s| s.processed),
+                ("cfp_files_failed_total", "Files that failed processing", |s| s.failed),
+                ("cfp_bytes_in_total", "Bytes read from processed input files", |s| s.bytes_in),
+                ("cfp_bytes_out_total", "Bytes written to output files", |s| s.bytes_out),
+            ];
+            for (name, help, value) in per_strategy {
+                header(&mut out, name, "counter", help);
+                for (strategy, metrics) in strategies.iter() {
+                    sample(&mut out, name, &[("strategy", strategy)], value(metrics));
+                }
+            }
+
+            let name = "cfp_processing_duration_seconds";
+            header(&mut out, name, "histogram", "Time spent in the processing strategy");
+            for (strategy, metrics) in strategies.iter() {
+                let histogram = &metrics.duration;
+                for (bound, count) in DURATION_BUCKETS.iter().zip(histogram.buckets) {
+                    sample(&mut out, &format!("{}_bucket", name), &[("strategy", strategy), ("le", &bound.to_string())], count);
+                }
+                sample(&mut out, &format!("{}_bucket", name), &[("strategy", strategy), ("le", "+Inf")], histogram.count);
+                sample(&mut out, &format!("{}_sum", name), &[("strategy", strategy)], histogram.sum);
+                sample(&mut out, &format!("{}_count", name), &[("strategy", strategy)], histogram.count);
+            }
+        }
+
+        header(&mut out, "cfp_file_retries_total", "counter", "Retries while waiting for locked input files");
+        sample(&mut out, "cfp_file_retries_total", &[], self.file_retries.load(Ordering::Relaxed));
+
+        header(&mut out, "cfp_job_retries_total", "counter", "Jobs retried after failing with a retryable error");
+        sample(&mut out, "cfp_job_retries_total", &[], self.job_retries.load(Ordering::Relaxed));
+
+        header(&mut out, "cfp_watcher_errors_total", "counter", "Watcher failures that required a restart");
+        sample(&mut out, "cfp_watcher_errors_total", &[], self.watcher_errors.load(Ordering::Relaxed));
+
+        header(&mut out, "cfp_jobs_in_flight", "gauge", "Files currently being processed");
+        sample(&mut out, "cfp_jobs_in_flight", &[], gauges.in_flight);
+
+        let queue = &gauges.queue;
+        header(&mut out, "cfp_queue_depth", "gauge", "Files waiting in the job queue");
+        sample(&mut out, "cfp_queue_depth", &[], queue.depth);
+        header(&mut out, "cfp_queue_max_depth", "gauge", "Largest job queue depth observed");
+        sample(&mut out, "cfp_queue_max_depth", &[], queue.max_depth);
+        header(&mut out, "cfp_queue_enqueued_total", "counter", "Entries accepted into the job queue");
+        sample(&mut out, "cfp_queue_enqueued_total", &[], queue.enqueued);
+        header(&mut out, "cfp_queue_dropped_total", "counter", "Paths or watcher events dropped because the queue was full");
+        sample(&mut out, "cfp_queue_dropped_total", &[], queue.dropped);
+        header(&mut out, "cfp_queue_spills_total", "counter", "Times the job queue overflowed and scheduled a rescan");
+        sample(&mut out, "cfp_queue_spills_total", &[], queue.spills);
+
+        out
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: impl std::fmt::Display) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels.iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::job::JobId;
+    use std::path::PathBuf;
+
+    fn processed(strategy: &str, size: usize, processing_time_ms: u64) -> ProcessingResult {
+        ProcessingResult {
+            job_id: JobId::new(),
+            input_file: PathBuf::from("/in/a.txt"),
+            output_file: PathBuf::from("/out/a.txt"),
+            original_size: size,
+            processed_size: size * 2,
+            processing_time_ms,
+            started_at_ms: 0,
+            strategy: strategy.to_string(),
+            strategy_info: None,
+        }
+    }
+
+    #[test]
+    fn renders_counters_with_help_type_and_labels() {
+        let metrics = Metrics::new();
+        metrics.record_detected(DetectionSource::Watcher, 3);
+        metrics.record_detected(DetectionSource::Scan, 1);
+        metrics.record_processed(&processed("hash", 10, 20));
+        metrics.record_processed(&processed("hash", 5, 2000));
+        metrics.record_failed("wasm");
+        metrics.record_file_retry();
+        metrics.record_file_retry();
+        metrics.record_job_retry();
+        let gauges = Gauges { in_flight: 2, ..Gauges::default() };
+
+        let out = metrics.render(&gauges);
+        let lines: Vec<&str> = out.lines().collect();
+        for expected in [
+            "# HELP cfp_files_detected_total Files queued for processing, by how they were found",
+            "# TYPE cfp_files_detected_total counter",
+            "cfp_files_detected_total{source=\"watcher\"} 3",
+            "cfp_files_detected_total{source=\"scan\"} 1",
+            "cfp_files_processed_total{strategy=\"hash\"} 2",
+            "cfp_files_processed_total{strategy=\"wasm\"} 0",
+            "cfp_files_failed_total{strategy=\"wasm\"} 1",
+            "cfp_bytes_in_total{strategy=\"hash\"} 15",
+            "cfp_bytes_out_total{strategy=\"hash\"} 30",
+            "# TYPE cfp_processing_duration_seconds histogram",
+            "cfp_processing_duration_seconds_bucket{strategy=\"hash\",le=\"0.025\"} 1",
+            "cfp_processing_duration_seconds_bucket{strategy=\"hash\",le=\"2.5\"} 2",
+            "cfp_processing_duration_seconds_bucket{strategy=\"hash\",le=\"+Inf\"} 2",
+            "cfp_processing_duration_seconds_count{strategy=\"hash\"} 2",
+            "# HELP cfp_file_retries_total Retries while waiting for locked input files",
+            "# TYPE cfp_file_retries_total counter",
+            "cfp_file_retries_total 2",
+            "# HELP cfp_job_retries_total Jobs retried after failing with a retryable error",
+            "# TYPE cfp_job_retries_total counter",
+            "cfp_job_retries_total 1",
+            "# TYPE cfp_jobs_in_flight gauge",
+            "cfp_jobs_in_flight 2",
+        ] {
+            assert!(lines.contains(&expected), "missing {:?} in:\n{}", expected, out);
+        }
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        let mut out = String::new();
+        sample(&mut out, "cfp_test", &[("strategy", "a\"b\\c\nd")], 1);
+        assert_eq!(out, "cfp_test{strategy=\"a\\\"b\\\\c\\nd\"} 1\n");
+    }
+}
@@ -1,21 +1,49 @@
 use crate::error::AppError;
-use crate::logging::{log_info, log_error, log_info_simple, log_error_simple};
-use crate::config::ProcessingConfig;
+use crate::logging::{log_info, log_error, log_debug, log_info_simple, log_error_simple, log_fields};
+use crate::config::{ProcessingConfig, TriggerConfig, WebhookConfig};
+use crate::trigger::{resolve_ready_files, ReadyMarker};
+use crate::queue::{JobQueue, QueueEntry};
+use crate::health;
+use crate::metrics::metrics;
+use crate::job::{current_job, in_job, in_job_blocking, JobContext, JobId, JobTracker};
 use crate::utils::{
     wait_for_file_availability, read_file_content, extract_file_name,
     write_processed_data, remove_original_file, scan_input_directory,
-    hash_file_content
+    hash_file_content, format_file_size, unix_millis
 };
+use crate::webhook::WebhookNotifier;
+use crate::command::CommandStrategy;
+use crate::script::ScriptStrategy;
+use crate::wasm::{plugin_names, plugin_path, WasmStrategy};
+use crate::results::{append_to_manifest, write_sidecar, ResultRecord};
 
+use futures::stream::{self, StreamExt};
+use log::kv::Value;
+use log::Level;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
-use tokio::task;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use tokio::task::{self, JoinHandle};
+use tokio::time::sleep;
 
 /// Trait for defining file processing strategies
 /// This allows for flexible implementation of different processing logic
 pub trait ProcessingStrategy: Send + Sync {
     /// Process file content and return processed data as string
     fn process_content(&self, file_name: &str, content: &[u8]) -> Result<String, AppError>;
+
+    /// Process file content and return the processed data together with optional
+    /// information about the run, reported as `strategy_info`; defaults to
+    /// `process_content` without information
+    fn process_with_info(&self, file_name: &str, content: &[u8]) -> Result<(String, Option<String>), AppError> {
+        Ok((self.process_content(file_name, content)?, None))
+    }
+
+    /// Name of the strategy used in logs; defaults to the type name
+    fn name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
 }
 
 /// Metadata for file processing (general for any processing strategy)
@@ -32,9 +60,12 @@ pub struct ProcessingMetadata {
 pub struct HashProcessingStrategy;
 
 impl ProcessingStrategy for HashProcessingStrategy {
+    fn name(&self) -> &str {
+        "hash"
+    }
+
     fn process_content(&self, file_name: &str, content: &[u8]) -> Result<String, AppError> {
         use sha2::{Sha256, Digest};
-        use hex;
         
         let hash = {
             let mut hasher = Sha256::new();
@@ -59,6 +90,10 @@ impl ProcessingStrategy for HashProcessingStrategy {
 pub struct TextAnalysisStrategy;
 
 impl ProcessingStrategy for TextAnalysisStrategy {
+    fn name(&self) -> &str {
+        "text_analysis"
+    }
+
     fn process_content(&self, file_name: &str, content: &[u8]) -> Result<String, AppError> {
         // Convert bytes to string (assuming UTF-8)
         let text = String::from_utf8_lossy(content);
@@ -81,40 +116,161 @@ impl ProcessingStrategy for TextAnalysisStrategy {
     }
 }
 
+/// Names of the built-in processing strategies, selectable via `processing.strategy`
+pub const STRATEGY_NAMES: &[&str] = &["hash", "text_analysis", "command", "script"];
+
+/// Create the processing strategy named in `config.strategy`: a built-in one, or
+/// otherwise the WebAssembly plugin of that name
+pub fn strategy_from_config(config: &ProcessingConfig) -> Result<Arc<dyn ProcessingStrategy>, AppError> {
+    match config.strategy.as_str() {
+        "hash" => Ok(Arc::new(HashProcessingStrategy)),
+        "text_analysis" => Ok(Arc::new(TextAnalysisStrategy)),
+        "command" => Ok(Arc::new(CommandStrategy::new(&config.command)?)),
+        "script" => Ok(Arc::new(ScriptStrategy::load(&config.script)?)),
+        name if plugin_path(name, &config.wasm).is_some() => Ok(Arc::new(WasmStrategy::load(name, &config.wasm)?)),
+        name => {
+            let plugins = plugin_names(&config.wasm);
+            Err(AppError::config(format!(
+                "Unknown processing strategy '{}' (built-in: {}; plugins in {}: {})",
+                name,
+                STRATEGY_NAMES.join(", "),
+                config.wasm.plugins_dir,
+                if plugins.is_empty() { "none".to_string() } else { plugins.join(", ") }
+            )))
+        }
+    }
+}
+
 /// Represents the result of file processing
 #[derive(Debug)]
 pub struct ProcessingResult {
+    /// ID of the job that processed the file, as it appears in the logs
+    pub job_id: JobId,
     pub input_file: PathBuf,
     pub output_file: PathBuf,
     pub original_size: usize,
     pub processed_size: usize,
     pub processing_time_ms: u64,
+    /// Job start in milliseconds since the Unix epoch
+    pub started_at_ms: u64,
+    pub strategy: String,
     pub strategy_info: Option<String>,
 }
 
+/// Summary of a batch of processed files
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub succeeded: usize,
+    pub failures: Vec<(PathBuf, String)>,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub elapsed_ms: u64,
+}
+
+impl BatchReport {
+    fn record_success(&mut self, result: &ProcessingResult) {
+        self.succeeded += 1;
+        self.bytes_in += result.original_size as u64;
+        self.bytes_out += result.processed_size as u64;
+    }
+
+    fn record_failure(&mut self, path: PathBuf, error: String) {
+        self.failures.push((path, error));
+    }
+
+    /// Whether every file in the batch was processed successfully
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+impl std::fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Processed: {}", self.succeeded)?;
+        writeln!(f, "Failed: {}", self.failures.len())?;
+        writeln!(f, "Bytes in: {}", format_file_size(self.bytes_in))?;
+        writeln!(f, "Bytes out: {}", format_file_size(self.bytes_out))?;
+        write!(f, "Time: {}ms", self.elapsed_ms)?;
+        for (path, error) in &self.failures {
+            write!(f, "\n  FAILED {}: {}", path.display(), error)?;
+        }
+        Ok(())
+    }
+}
+
 /// Main file processor that handles file operations and processing
 pub struct FileProcessor {
     config: ProcessingConfig,
     strategy: Arc<dyn ProcessingStrategy>,
+    /// Files currently being processed, shared between clones so that a file
+    /// reported by both the watcher and a rescan is only processed once
+    in_flight: Arc<Mutex<HashSet<PathBuf>>>,
+    /// Decides when detected files are ready for processing
+    trigger: TriggerConfig,
+    /// Running and recently failed jobs, shared like `in_flight`
+    jobs: Arc<JobTracker>,
+    /// Sends webhooks about finished jobs, if any target is configured
+    webhooks: Option<Arc<WebhookNotifier>>,
 }
 
 impl FileProcessor {
-    pub fn new(config: ProcessingConfig) -> Self {
-        Self { 
-            config,
-            //strategy: Arc::new(HashProcessingStrategy),
-            strategy: Arc::new(TextAnalysisStrategy),
-        }
+    /// Create a file processor using the strategy named in the configuration
+    pub fn new(config: ProcessingConfig) -> Result<Self, AppError> {
+        let strategy = strategy_from_config(&config)?;
+        Ok(Self::with_strategy(config, strategy))
     }
     
     /// Create a file processor with a custom processing strategy
     pub fn with_strategy(config: ProcessingConfig, strategy: Arc<dyn ProcessingStrategy>) -> Self {
-        Self { config, strategy }
+        Self {
+            config,
+            strategy,
+            in_flight: Arc::new(Mutex::new(HashSet::new())),
+            trigger: TriggerConfig::default(),
+            jobs: Arc::new(JobTracker::default()),
+            webhooks: None,
+        }
+    }
+
+    /// Use the given trigger configuration to decide when files are ready
+    pub fn with_trigger(mut self, trigger: TriggerConfig) -> Self {
+        self.trigger = trigger;
+        self
+    }
+
+    /// Send webhooks about finished jobs to the configured targets
+    pub fn with_webhooks(mut self, webhook: &WebhookConfig) -> Result<Self, AppError> {
+        self.webhooks = WebhookNotifier::new(webhook)?.map(Arc::new);
+        Ok(self)
     }
 
-    /// Process a single file from input to output directory
+    /// Create a processor with new settings that shares the in-flight set and
+    /// job history of this one, so files already being processed are not picked up twice.
+    /// The strategy is looked up again from `config.strategy`.
+    pub fn reconfigure(&self, config: ProcessingConfig, trigger: TriggerConfig, webhook: &WebhookConfig) -> Result<Self, AppError> {
+        let strategy = strategy_from_config(&config)?;
+        let webhooks = WebhookNotifier::new(webhook)?.map(Arc::new);
+        Ok(Self { config, strategy, in_flight: Arc::clone(&self.in_flight), trigger, jobs: Arc::clone(&self.jobs), webhooks })
+    }
+
+    /// Process a single file from input to output directory.
+    /// Runs as a new job unless the calling task already works on a job for this file.
     pub async fn process_file(&self, file_path: &Path, output_dir: &Path) -> Result<ProcessingResult, AppError> {
-        log_info("Processing file", &format!("{}", file_path.display()));
+        match current_job() {
+            Some(job) if job.file == file_path => self.run_job(job.id, file_path, output_dir).await,
+            _ => {
+                let job = JobContext::new(file_path);
+                let job_id = job.id;
+                in_job(job, self.run_job(job_id, file_path, output_dir)).await
+            }
+        }
+    }
+
+    async fn run_job(&self, job_id: JobId, file_path: &Path, output_dir: &Path) -> Result<ProcessingResult, AppError> {
+        let started_at_ms = unix_millis(SystemTime::now());
+        log_fields(Level::Info, "Processing file", &format!("{}", file_path.display()), &[
+            ("file", Value::from_display(&file_path.display())),
+        ]);
 
         wait_for_file_availability(file_path, &self.config).await?;
 
@@ -125,88 +281,286 @@ impl FileProcessor {
         
         // Measure processing time
         let start_time = std::time::Instant::now();
-        let processed_data = self.process_content_in_background(&file_name, &file_content).await?;
+        let (processed_data, strategy_info) = self.process_content_in_background(&file_name, &file_content).await?;
         let processing_time = start_time.elapsed();
         
         let output_path = write_processed_data(&file_name, &processed_data, output_dir, &self.config.output_extension).await?;
         
-        remove_original_file(file_path).await?;
-
-        log_info("Successfully processed file", &format!("{}", file_path.display()));
-        
         // Create general metadata
         let metadata = ProcessingMetadata {
             original_size,
             processed_size: processed_data.len(),
             processing_time_ms: processing_time.as_millis() as u64,
-            strategy_info: None, // Strategies can set this if they want
+            strategy_info,
         };
         
-        Ok(ProcessingResult {
+        let result = ProcessingResult {
+            job_id,
             input_file: file_path.to_path_buf(),
             output_file: output_path,
             original_size: metadata.original_size,
             processed_size: metadata.processed_size,
             processing_time_ms: metadata.processing_time_ms,
+            started_at_ms,
+            strategy: self.strategy.name().to_string(),
             strategy_info: metadata.strategy_info,
-        })
+        };
+
+        // Written before the original is removed, so a failed write leaves the file to be processed again
+        if self.config.write_sidecar || self.config.write_manifest {
+            let record = ResultRecord::new(&result, &file_content, processed_data.as_bytes(), unix_millis(SystemTime::now()));
+            if self.config.write_sidecar {
+                write_sidecar(&record).await?;
+            }
+            if self.config.write_manifest {
+                append_to_manifest(&record, &output_dir.join(&self.config.manifest_file)).await?;
+            }
+        }
+
+        remove_original_file(file_path).await?;
+
+        log_fields(Level::Info, "Successfully processed file", &format!("{}", file_path.display()), &[
+            ("file", Value::from_display(&file_path.display())),
+        ]);
+
+        Ok(result)
     }
 
-    /// Process all existing files in the input directory
-    pub async fn process_initial_files(&self, input_dir: &Path, output_dir: &Path) -> Result<(), AppError> {
+    /// Process all existing files in the input directory, at most `workers` at a
+    /// time, and wait until they are done
+    pub async fn process_initial_files(&self, input_dir: &Path, output_dir: &Path, recursive: bool, workers: usize) -> Result<BatchReport, AppError> {
         log_info_simple("Processing existing files in input directory...");
         
-        let existing_files = scan_input_directory(input_dir).await?;
+        let existing_files = scan_input_directory(input_dir, recursive).await?;
         
         if !existing_files.is_empty() {
             log_info("Found existing files to process", &format!("{} files", existing_files.len()));
-            self.spawn_processing_tasks(existing_files, output_dir).await;
+            Ok(self.process_paths(existing_files, output_dir, "Existing file processing", workers).await)
         } else {
             log_info_simple("No existing files found in input directory.");
+            Ok(BatchReport::default())
         }
+    }
 
-        Ok(())
+    /// Process the given files, at most `workers` at a time, and wait until all of them are done.
+    /// Files are claimed only when a worker is free for them.
+    pub async fn process_paths(&self, files: Vec<PathBuf>, output_dir: &Path, context: &'static str, workers: usize) -> BatchReport {
+        let start_time = std::time::Instant::now();
+        let outcomes: Vec<_> = stream::iter(files)
+            .flat_map(|file_path| stream::iter(self.claim_ready(&file_path)
+                .into_iter()
+                .flat_map(|(files, marker)| files.into_iter().map(move |path| (path, marker.clone())))))
+            .map(|(path, marker)| {
+                let handle = self.spawn_claimed(path.clone(), marker, output_dir, context);
+                async move { (path, handle.await) }
+            })
+            .buffer_unordered(workers.max(1))
+            .collect()
+            .await;
+
+        let mut report = BatchReport::default();
+        for (path, outcome) in outcomes {
+            match outcome {
+                Ok(Ok(result)) => report.record_success(&result),
+                Ok(Err(e)) => report.record_failure(path, e.to_string()),
+                Err(e) => {
+                    log_error("Processing task failed", &e);
+                    report.record_failure(path, format!("{:#}", e));
+                }
+            }
+        }
+        report.elapsed_ms = start_time.elapsed().as_millis() as u64;
+        report
     }
 
     // Private helper methods
 
     /// Process content in background thread (CPU-intensive operations)
-    async fn process_content_in_background(&self, file_name: &str, content: &[u8]) -> Result<String, AppError> {
+    async fn process_content_in_background(&self, file_name: &str, content: &[u8]) -> Result<(String, Option<String>), AppError> {
         let content_clone = content.to_vec();
         let file_name_clone = file_name.to_string();
         let strategy_clone = Arc::clone(&self.strategy);
+        let job = current_job();
         
-        task::spawn_blocking(move || {
+        task::spawn_blocking(move || in_job_blocking(job, || {
             log_info("Starting CPU-bound processing", &format!("for '{}' on a blocking thread", file_name_clone));
             
-            let result = strategy_clone.process_content(&file_name_clone, &content_clone);
+            let result = strategy_clone.process_with_info(&file_name_clone, &content_clone);
             
             log_info("Finished CPU-bound processing", &format!("for '{}'", file_name_clone));
             result
-        }).await.map_err(|e| {
-            let msg = format!("Blocking task failed: {}", e);
-            log_error_simple(&msg);
-            AppError::Processing(msg)
+        })).await.map_err(|e| {
+            log_error_simple(&format!("Blocking task failed: {}", e));
+            AppError::Strategy {
+                strategy: self.strategy.name().to_string(),
+                file: file_name.to_string(),
+                message: "blocking task failed".to_string(),
+                source: Some(e.into()),
+            }
         })?
     }
 
-    async fn spawn_processing_tasks(&self, files: Vec<PathBuf>, output_dir: &Path) {
-        for file_path in files {
-            let output_dir_clone = output_dir.to_path_buf();
-            let processor = self.clone();
+    /// Spawn background tasks for the files a detected path makes ready.
+    /// Files that are already being processed are skipped.
+    pub fn spawn_processing_task(
+        &self,
+        file_path: PathBuf,
+        output_dir: &Path,
+        context: &'static str
+    ) -> Vec<(PathBuf, JoinHandle<Result<ProcessingResult, AppError>>)> {
+        let Some((files, marker)) = self.claim_ready(&file_path) else {
+            return Vec::new();
+        };
+
+        files.into_iter().map(|path| {
+            let handle = self.spawn_claimed(path.clone(), marker.clone(), output_dir, context);
+            (path, handle)
+        }).collect()
+    }
+
+    /// Spawn a background task processing a claimed file
+    fn spawn_claimed(
+        &self,
+        file_path: PathBuf,
+        marker: Option<Arc<ReadyMarker>>,
+        output_dir: &Path,
+        context: &'static str
+    ) -> JoinHandle<Result<ProcessingResult, AppError>> {
+        let output_dir_clone = output_dir.to_path_buf();
+        let processor = self.clone();
+        tokio::spawn(async move {
+            processor.run_claimed(file_path, marker, &output_dir_clone, context).await
+        })
+    }
 
-            tokio::spawn(async move {
-                log_info("Processing existing file", &format!("{}", file_path.display()));
-                match processor.process_file(&file_path, &output_dir_clone).await {
-                    Ok(result) => {
-                        log_processing_result("Existing file processing", &result);
+    /// Claim the files a detected path makes ready for processing.
+    /// Depending on the trigger mode this is the path itself, the data file of a
+    /// marker, or every file released by a batch marker. Files that are already
+    /// being processed are skipped, so the same path may safely be reported more
+    /// than once (e.g. by the watcher and a rescan).
+    fn claim_ready(&self, file_path: &Path) -> Option<(Vec<PathBuf>, Option<Arc<ReadyMarker>>)> {
+        let ready = resolve_ready_files(file_path, &self.trigger)?;
+
+        if ready.files.is_empty() {
+            if let Some(path) = ready.marker {
+                // An empty batch is complete as soon as it is released
+                let marker = ReadyMarker::new(path, 0);
+                tokio::spawn(async move { marker.consume().await });
+            }
+            return None;
+        }
+
+        // Files rejected here are in flight or failed for good; their marker is left
+        // to the jobs already holding it, or kept so the files can be released again
+        let claimed: Vec<PathBuf> = ready.files.into_iter().filter(|path| self.claim(path)).collect();
+        if claimed.is_empty() {
+            return None;
+        }
+        let marker = ready.marker.map(|path| Arc::new(ReadyMarker::new(path, claimed.len())));
+        Some((claimed, marker))
+    }
+
+    /// Process a claimed file, then complete its marker and release the claim
+    async fn run_claimed(
+        &self,
+        file_path: PathBuf,
+        marker: Option<Arc<ReadyMarker>>,
+        output_dir: &Path,
+        context: &str
+    ) -> Result<ProcessingResult, AppError> {
+        let job = JobContext::new(&file_path);
+        let job_id = job.id;
+        self.jobs.start(job_id, &file_path, self.strategy.name());
+        in_job(job, async {
+            let outcome = self.process_with_retries(&file_path, output_dir).await;
+            self.jobs.finish(job_id, outcome.as_ref().err());
+            match &outcome {
+                Ok(result) => {
+                    metrics().record_processed(result);
+                    health::record_success();
+                    if let Some(webhooks) = &self.webhooks {
+                        webhooks.completed(result);
                     }
-                    Err(e) => {
-                        log_error("Failed to process existing file", &format!("{}: {}", file_path.display(), e));
+                    log_processing_result(context, result);
+                }
+                Err(e) => {
+                    metrics().record_failed(self.strategy.name());
+                    if let Some(webhooks) = &self.webhooks {
+                        webhooks.failed(job_id, &file_path, self.strategy.name(), e);
                     }
+                    log_fields(Level::Error, &format!("{} failed", context), &format!("{}: {:#}", file_path.display(), e), &[
+                        ("file", Value::from_display(&file_path.display())),
+                        ("strategy", Value::from(self.strategy.name())),
+                        ("error", Value::from_display(e)),
+                        ("error_code", Value::from(e.code())),
+                    ]);
                 }
-            });
+            }
+            if let Some(marker) = marker {
+                marker.complete(outcome.is_ok()).await;
+            }
+            self.in_flight.lock().unwrap().remove(&file_path);
+            outcome
+        }).await
+    }
+
+    /// Process a file, running the job again with exponential backoff while it
+    /// fails with a retryable error, at most `job_retries` times
+    async fn process_with_retries(&self, file_path: &Path, output_dir: &Path) -> Result<ProcessingResult, AppError> {
+        let mut retry_count = 0;
+        let mut delay = Duration::from_millis(self.config.initial_retry_delay_ms);
+        loop {
+            match self.process_file(file_path, output_dir).await {
+                Err(e) if e.is_retryable() && retry_count < self.config.job_retries => {
+                    retry_count += 1;
+                    log_fields(Level::Warn, "Job failed, retrying", &format!("{} (retry {}/{}): {:#}",
+                        file_path.display(), retry_count, self.config.job_retries, e), &[
+                        ("file", Value::from_display(&file_path.display())),
+                        ("attempt", Value::from(retry_count)),
+                        ("max_attempts", Value::from(self.config.job_retries)),
+                        ("error_code", Value::from(e.code())),
+                    ]);
+                    metrics().record_job_retry();
+                    sleep(delay).await;
+                    delay = Duration::min(delay * 2, Duration::from_secs(self.config.max_retry_delay_sec));
+                }
+                outcome => return outcome,
+            }
+        }
+    }
+
+    /// Number of files currently being processed
+    pub fn in_flight_count(&self) -> usize {
+        self.in_flight.lock().unwrap().len()
+    }
+
+    /// Running and recently failed jobs
+    pub fn jobs(&self) -> &JobTracker {
+        &self.jobs
+    }
+
+    /// Mark a file as in flight; returns false if it is already being processed, no longer
+    /// exists, or failed with a non-retryable error and has not been modified since
+    fn claim(&self, file_path: &Path) -> bool {
+        if !self.in_flight.lock().unwrap().insert(file_path.to_path_buf()) {
+            log_debug("File is already being processed, skipping", &format!("{}", file_path.display()));
+            return false;
+        }
+        if !file_path.exists() {
+            // Already processed and removed between being listed and being dispatched
+            log_debug("File no longer exists, skipping", &format!("{}", file_path.display()));
+            self.in_flight.lock().unwrap().remove(file_path);
+            return false;
         }
+        // Rescans report every file left in the input directory; one that failed for
+        // good stays there and is only worth another attempt once it has changed
+        let metadata = std::fs::metadata(file_path).and_then(|m| Ok((unix_millis(m.modified()?), m.len())));
+        if let Ok((modified_ms, size)) = metadata && self.jobs.failed_permanently_since(file_path, modified_ms, size) {
+            log_debug("File failed with a non-retryable error and has not changed since, skipping", &format!("{}", file_path.display()));
+            self.in_flight.lock().unwrap().remove(file_path);
+            return false;
+        }
+        true
     }
 }
 
@@ -216,6 +570,63 @@ impl Clone for FileProcessor {
         Self {
             config: self.config.clone(),
             strategy: Arc::clone(&self.strategy),
+            in_flight: Arc::clone(&self.in_flight),
+            trigger: self.trigger.clone(),
+            jobs: Arc::clone(&self.jobs),
+            webhooks: self.webhooks.clone(),
+        }
+    }
+}
+
+/// Processor used by the service workers, replaceable while the service runs.
+/// Jobs that already started keep the processor they started with.
+#[derive(Clone)]
+pub struct SharedProcessor(Arc<RwLock<Arc<FileProcessor>>>);
+
+impl SharedProcessor {
+    pub fn new(processor: FileProcessor) -> Self {
+        Self(Arc::new(RwLock::new(Arc::new(processor))))
+    }
+
+    /// The processor new jobs are started with
+    pub fn current(&self) -> Arc<FileProcessor> {
+        Arc::clone(&self.0.read().unwrap())
+    }
+
+    /// Use `processor` for every job started from now on
+    pub fn replace(&self, processor: FileProcessor) {
+        *self.0.write().unwrap() = Arc::new(processor);
+    }
+
+    /// Take entries from the job queue and process them until the queue is closed.
+    /// Files released by a batch marker are put back into the queue individually,
+    /// so a large batch is spread across all workers.
+    pub async fn run_worker(&self, queue: Arc<JobQueue>, output_dir: PathBuf) {
+        while let Some(entry) = queue.pop().await {
+            // Each job uses the processor that is current when it starts
+            let processor = self.current();
+            match entry {
+                QueueEntry::Detected(path) => {
+                    let Some((files, marker)) = processor.claim_ready(&path) else {
+                        continue;
+                    };
+                    match marker {
+                        Some(marker) if files.len() > 1 => {
+                            for file in files {
+                                queue.push_released(file, Arc::clone(&marker));
+                            }
+                        }
+                        marker => {
+                            for file in files {
+                                let _ = processor.run_claimed(file, marker.clone(), &output_dir, "File processing").await;
+                            }
+                        }
+                    }
+                }
+                QueueEntry::Released { path, marker } => {
+                    let _ = processor.run_claimed(path, Some(marker), &output_dir, "Batch file processing").await;
+                }
+            }
         }
     }
 }
@@ -223,13 +634,82 @@ impl Clone for FileProcessor {
 /// Helper function to log processing results consistently
 pub fn log_processing_result(context: &str, result: &ProcessingResult) {
     let strategy_info = result.strategy_info.as_deref().unwrap_or("None");
-    log_info(&format!("{} completed successfully", context), &format!(
-        "Input: {}, Output: {}, Original: {} bytes, Processed: {} bytes, Time: {}ms, Strategy Info: {}",
+    let (input_file, output_file) = (result.input_file.display(), result.output_file.display());
+    let mut fields = vec![
+        ("file", Value::from_display(&input_file)),
+        ("output_file", Value::from_display(&output_file)),
+        ("original_size", Value::from(result.original_size)),
+        ("processed_size", Value::from(result.processed_size)),
+        ("duration_ms", Value::from(result.processing_time_ms)),
+        ("strategy", Value::from(result.strategy.as_str())),
+    ];
+    if let Some(info) = &result.strategy_info {
+        fields.push(("strategy_info", Value::from(info.as_str())));
+    }
+    log_fields(Level::Info, &format!("{} completed successfully", context), &format!(
+        "Input: {}, Output: {}, Original: {} bytes, Processed: {} bytes, Time: {}ms, Strategy: {}, Strategy Info: {}",
         result.input_file.display(),
         result.output_file.display(),
         result.original_size,
         result.processed_size,
         result.processing_time_ms,
+        result.strategy,
         strategy_info
-    ));
-}
\ No newline at end of file
+    ), &fields);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TriggerMode;
+
+    fn marker_processor() -> FileProcessor {
+        let trigger = TriggerConfig { mode: TriggerMode::Marker, ..TriggerConfig::default() };
+        FileProcessor::with_strategy(ProcessingConfig::default(), Arc::new(HashProcessingStrategy)).with_trigger(trigger)
+    }
+
+    fn dir_with(names: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cfp-processor-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in names {
+            std::fs::write(dir.join(name), b"data").unwrap();
+        }
+        dir
+    }
+
+    #[tokio::test]
+    async fn marker_reported_again_while_its_file_is_in_flight_is_kept() {
+        let dir = dir_with(&["data.csv", "data.csv.done"]);
+        let processor = marker_processor();
+
+        let (claimed, marker) = processor.claim_ready(&dir.join("data.csv.done")).unwrap();
+        assert_eq!(claimed, [dir.join("data.csv")]);
+
+        // A rescan reports the data file and its marker again while the job runs
+        assert!(processor.claim_ready(&dir.join("data.csv")).is_none());
+        assert!(processor.claim_ready(&dir.join("data.csv.done")).is_none());
+        tokio::task::yield_now().await;
+        assert!(dir.join("data.csv.done").exists());
+
+        // The running job's failure keeps the marker for a later release
+        marker.unwrap().complete(false).await;
+        processor.in_flight.lock().unwrap().remove(&dir.join("data.csv"));
+        assert!(dir.join("data.csv.done").exists());
+        assert!(processor.claim_ready(&dir.join("data.csv.done")).is_some());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn empty_batch_marker_is_consumed() {
+        let dir = dir_with(&["_SUCCESS"]);
+        assert!(marker_processor().claim_ready(&dir.join("_SUCCESS")).is_none());
+        for _ in 0..100 {
+            if !dir.join("_SUCCESS").exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!dir.join("_SUCCESS").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
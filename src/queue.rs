@@ -0,0 +1,502 @@
+use crate::config::{QueueConfig, QueueOrdering};
+use crate::logging::{log_info, log_warning, log_debug};
+use crate::trigger::ReadyMarker;
+
+use std::cmp::Ordering as CmpOrdering;
+use std::collections::{BinaryHeap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use serde::Serialize;
+use tokio::sync::Notify;
+
+/// An entry waiting in the job queue
+#[derive(Debug)]
+pub enum QueueEntry {
+    /// A path reported by the watcher or a scan; not yet checked for readiness
+    Detected(PathBuf),
+    /// A file released by a batch marker, already claimed for processing
+    Released { path: PathBuf, marker: Arc<ReadyMarker> },
+}
+
+impl QueueEntry {
+    pub fn path(&self) -> &PathBuf {
+        match self {
+            QueueEntry::Detected(path) => path,
+            QueueEntry::Released { path, .. } => path,
+        }
+    }
+}
+
+/// Snapshot of the queue counters
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct QueueStats {
+    /// Entries currently waiting
+    pub depth: usize,
+    /// Largest depth observed
+    pub max_depth: usize,
+    /// Entries accepted into the queue
+    pub enqueued: u64,
+    /// Paths or watcher events dropped because the queue or the event channel was full
+    pub dropped: u64,
+    /// Times the queue overflowed and scheduled a rescan
+    pub spills: u64,
+}
+
+/// A queued file, as listed by `JobQueue::snapshot`
+#[derive(Debug, Clone, Serialize)]
+pub struct QueuedFile {
+    pub file: PathBuf,
+    pub priority: i64,
+    /// Released by a batch marker and already claimed for processing
+    pub released: bool,
+}
+
+/// Decides the order in which queued files are processed
+struct Prioritizer {
+    /// Directories of priority-encoding paths are only read below this directory
+    input_dir: PathBuf,
+    ordering: QueueOrdering,
+    path_priority_prefix: String,
+    rules: Vec<(glob::Pattern, i64)>,
+}
+
+impl Prioritizer {
+    fn new(config: &QueueConfig, input_dir: &Path) -> Self {
+        let rules = config.priority_rules.iter().filter_map(|rule| {
+            match glob::Pattern::new(&rule.pattern) {
+                Ok(pattern) => Some((pattern, rule.priority)),
+                Err(e) => {
+                    log_warning("Ignoring invalid priority rule", &format!("{}: {}", rule.pattern, e));
+                    None
+                }
+            }
+        }).collect();
+
+        Self {
+            input_dir: input_dir.to_path_buf(),
+            ordering: config.ordering,
+            path_priority_prefix: config.path_priority_prefix.clone(),
+            rules,
+        }
+    }
+
+    /// Highest priority from the matching rules and the priority encoded in the path
+    fn priority(&self, path: &Path) -> i64 {
+        let file_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+
+        let rule_priority = self.rules.iter()
+            .filter(|(pattern, _)| pattern.matches(&file_name))
+            .map(|(_, priority)| *priority)
+            .max();
+
+        rule_priority.into_iter().chain(self.path_priority(path)).max().unwrap_or(0)
+    }
+
+    /// Priority encoded in a file or directory name below the input directory,
+    /// e.g. `priority-5_report.csv` or `priority-5/report.csv`
+    fn path_priority(&self, path: &Path) -> Option<i64> {
+        if self.path_priority_prefix.is_empty() {
+            return None;
+        }
+        // Directories above the input directory say nothing about the file
+        let relative = path.strip_prefix(&self.input_dir)
+            .ok()
+            .or_else(|| path.file_name().map(Path::new))?;
+        relative.components()
+            .filter_map(|component| {
+                let name = component.as_os_str().to_string_lossy();
+                let rest = name.strip_prefix(&self.path_priority_prefix)?;
+                let digits: String = rest.chars().take_while(|c| c.is_ascii_digit()).collect();
+                digits.parse().ok()
+            })
+            .max()
+    }
+
+    /// Tie-breaker between files of equal priority; lower is processed first
+    fn order_key(&self, path: &Path) -> u64 {
+        match self.ordering {
+            QueueOrdering::Fifo => 0,
+            QueueOrdering::SmallestFirst => std::fs::metadata(path).map(|m| m.len()).unwrap_or(u64::MAX),
+            QueueOrdering::OldestFirst => std::fs::metadata(path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|age| age.as_millis() as u64)
+                .unwrap_or(u64::MAX),
+        }
+    }
+}
+
+/// A queue entry with its position in the processing order
+struct QueuedItem {
+    priority: i64,
+    order_key: u64,
+    sequence: u64,
+    entry: QueueEntry,
+}
+
+// `BinaryHeap` pops the greatest item: highest priority, then lowest order key, then earliest arrival
+impl Ord for QueuedItem {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.priority.cmp(&other.priority)
+            .then_with(|| other.order_key.cmp(&self.order_key))
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for QueuedItem {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for QueuedItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for QueuedItem {}
+
+struct QueueState {
+    entries: BinaryHeap<QueuedItem>,
+    next_sequence: u64,
+    /// Detected paths currently queued, to avoid queueing the same path twice
+    queued: HashSet<PathBuf>,
+    max_depth: usize,
+    above_high_water: bool,
+}
+
+/// Bounded priority queue between file detection and the processing workers.
+/// Pushing never blocks, so it is safe to call from the notify thread; when the
+/// queue is full, paths are dropped and a rescan is requested instead, which
+/// picks the dropped files up again once the backlog has drained.
+pub struct JobQueue {
+    state: Mutex<QueueState>,
+    prioritizer: RwLock<Prioritizer>,
+    capacity: usize,
+    high_water_mark: usize,
+    available: Notify,
+    drained: Notify,
+    spill_pending: AtomicBool,
+    closed: AtomicBool,
+    /// While set, workers do not take new entries
+    paused: AtomicBool,
+    enqueued: AtomicU64,
+    dropped: AtomicU64,
+    spills: AtomicU64,
+}
+
+impl JobQueue {
+    /// Create a queue for the files detected in `input_dir`
+    pub fn new(config: &QueueConfig, input_dir: &Path) -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                entries: BinaryHeap::new(),
+                next_sequence: 0,
+                queued: HashSet::new(),
+                max_depth: 0,
+                above_high_water: false,
+            }),
+            prioritizer: RwLock::new(Prioritizer::new(config, input_dir)),
+            capacity: config.capacity,
+            high_water_mark: config.high_water_mark,
+            available: Notify::new(),
+            drained: Notify::new(),
+            spill_pending: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            enqueued: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            spills: AtomicU64::new(0),
+        }
+    }
+
+    /// Queue a detected path. Returns false if it was already queued or the queue is full.
+    pub fn push(&self, path: PathBuf) -> bool {
+        let rank = self.rank(&path);
+        let mut state = self.state.lock().unwrap();
+        if state.queued.contains(&path) {
+            log_debug("File is already queued, skipping", &format!("{}", path.display()));
+            return false;
+        }
+        if state.entries.len() >= self.capacity {
+            drop(state);
+            log_debug("Queue full, dropping path", &format!("{}", path.display()));
+            self.record_dropped();
+            return false;
+        }
+        state.queued.insert(path.clone());
+        self.insert(&mut state, QueueEntry::Detected(path), rank);
+        true
+    }
+
+    /// Queue a file released by a batch marker. Released files were already
+    /// claimed, so they are always accepted even if the queue is full.
+    pub fn push_released(&self, path: PathBuf, marker: Arc<ReadyMarker>) {
+        let rank = self.rank(&path);
+        let mut state = self.state.lock().unwrap();
+        self.insert(&mut state, QueueEntry::Released { path, marker }, rank);
+    }
+
+    /// Priority a path would be queued with; higher is processed first
+    pub fn priority(&self, path: &Path) -> i64 {
+        self.prioritizer.read().unwrap().priority(path)
+    }
+
+    /// Replace the ordering and priority rules. Entries already queued keep
+    /// their position; the new rules apply to entries queued from now on.
+    pub fn set_priorities(&self, config: &QueueConfig) {
+        let mut prioritizer = self.prioritizer.write().unwrap();
+        *prioritizer = Prioritizer::new(config, &prioritizer.input_dir);
+    }
+
+    /// Priority and order key of a path, computed before taking the queue lock.
+    /// Higher priorities go first, then lower order keys, then earlier arrivals.
+    pub(crate) fn rank(&self, path: &Path) -> (i64, u64) {
+        let prioritizer = self.prioritizer.read().unwrap();
+        (prioritizer.priority(path), prioritizer.order_key(path))
+    }
+
+    fn insert(&self, state: &mut QueueState, entry: QueueEntry, (priority, order_key): (i64, u64)) {
+        let item = QueuedItem {
+            priority,
+            order_key,
+            sequence: state.next_sequence,
+            entry,
+        };
+        state.next_sequence += 1;
+        state.entries.push(item);
+        state.max_depth = state.max_depth.max(state.entries.len());
+        self.enqueued.fetch_add(1, Ordering::Relaxed);
+
+        if !state.above_high_water && state.entries.len() >= self.high_water_mark {
+            state.above_high_water = true;
+            log_warning("Job queue reached high-water mark", &format!("{} queued (capacity {})", state.entries.len(), self.capacity));
+        }
+        self.available.notify_one();
+    }
+
+    /// Record a dropped path or watcher event and request a rescan
+    pub fn record_dropped(&self) {
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+        if !self.spill_pending.swap(true, Ordering::SeqCst) {
+            self.spills.fetch_add(1, Ordering::Relaxed);
+            log_warning("Job queue overflow", "dropping events; a rescan will run once the backlog drains");
+        }
+        self.drained.notify_one();
+    }
+
+    /// Wait for the next entry, also while the queue is paused.
+    /// Returns `None` once the queue has been closed.
+    pub async fn pop(&self) -> Option<QueueEntry> {
+        loop {
+            if self.is_closed() {
+                return None;
+            }
+            // Register for a wakeup before checking, so a push in between is not missed
+            let notified = self.available.notified();
+            if self.is_paused() {
+                notified.await;
+                continue;
+            }
+            if let Some(entry) = self.try_pop() {
+                return Some(entry);
+            }
+            notified.await;
+        }
+    }
+
+    fn try_pop(&self) -> Option<QueueEntry> {
+        let mut state = self.state.lock().unwrap();
+        let entry = state.entries.pop()?.entry;
+        if let QueueEntry::Detected(path) = &entry {
+            state.queued.remove(path);
+        }
+        if state.above_high_water && state.entries.len() < self.high_water_mark {
+            state.above_high_water = false;
+            log_info("Job queue below high-water mark", &format!("{} queued", state.entries.len()));
+        }
+        if state.entries.len() < self.high_water_mark && self.spill_pending.load(Ordering::SeqCst) {
+            self.drained.notify_one();
+        }
+        Some(entry)
+    }
+
+    /// Wait until the queue overflowed and has since drained below the high-water mark.
+    /// Returns false once the queue has been closed.
+    pub async fn wait_for_spill(&self) -> bool {
+        loop {
+            if self.is_closed() {
+                return false;
+            }
+            let notified = self.drained.notified();
+            if self.spill_pending.load(Ordering::SeqCst) && self.depth() < self.high_water_mark {
+                self.spill_pending.store(false, Ordering::SeqCst);
+                return true;
+            }
+            notified.await;
+        }
+    }
+
+    /// Stop accepting work and wake up every waiting worker
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.available.notify_waiters();
+        self.drained.notify_waiters();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Stop handing out entries; detected files keep being queued
+    pub fn pause(&self) {
+        if !self.paused.swap(true, Ordering::SeqCst) {
+            log_info("Job queue paused", &format!("{} queued", self.depth()));
+        }
+    }
+
+    /// Hand out entries again after `pause`
+    pub fn resume(&self) {
+        if self.paused.swap(false, Ordering::SeqCst) {
+            log_info("Job queue resumed", &format!("{} queued", self.depth()));
+            self.available.notify_waiters();
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// The first `limit` queued files, in the order they will be processed
+    pub fn snapshot(&self, limit: usize) -> Vec<QueuedFile> {
+        let state = self.state.lock().unwrap();
+        let mut items: Vec<&QueuedItem> = state.entries.iter().collect();
+        items.sort_by(|a, b| b.cmp(a));
+        items.into_iter().take(limit).map(|item| QueuedFile {
+            file: item.entry.path().clone(),
+            priority: item.priority,
+            released: matches!(item.entry, QueueEntry::Released { .. }),
+        }).collect()
+    }
+
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water_mark
+    }
+
+    /// Whether the queue is at its high-water mark or has dropped entries not yet rescanned
+    pub fn is_saturated(&self) -> bool {
+        self.depth() >= self.high_water_mark || self.spill_pending.load(Ordering::SeqCst)
+    }
+
+    pub fn depth(&self) -> usize {
+        self.state.lock().unwrap().entries.len()
+    }
+
+    pub fn stats(&self) -> QueueStats {
+        let state = self.state.lock().unwrap();
+        QueueStats {
+            depth: state.entries.len(),
+            max_depth: state.max_depth,
+            enqueued: self.enqueued.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+            spills: self.spills.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Helper function to log queue statistics consistently
+pub fn log_queue_stats(stats: &QueueStats) {
+    log_info("Job queue statistics", &format!(
+        "Depth: {}, Max depth: {}, Enqueued: {}, Dropped: {}, Spills: {}",
+        stats.depth,
+        stats.max_depth,
+        stats.enqueued,
+        stats.dropped,
+        stats.spills
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::PriorityRule;
+
+    fn queue(configure: impl FnOnce(&mut QueueConfig)) -> JobQueue {
+        let mut config = QueueConfig::default();
+        configure(&mut config);
+        JobQueue::new(&config, Path::new("/in"))
+    }
+
+    fn drain(queue: &JobQueue) -> Vec<PathBuf> {
+        std::iter::from_fn(|| queue.try_pop()).map(|entry| entry.path().clone()).collect()
+    }
+
+    #[test]
+    fn higher_priorities_go_first_then_arrival_order() {
+        let queue = queue(|config| {
+            config.priority_rules = vec![
+                PriorityRule { pattern: "urgent_*".to_string(), priority: 10 },
+                PriorityRule { pattern: "*.csv".to_string(), priority: 5 },
+            ];
+        });
+        for name in ["a.txt", "b.csv", "urgent_c.txt", "d.txt", "urgent_e.csv"] {
+            assert!(queue.push(Path::new("/in").join(name)));
+        }
+        assert_eq!(queue.priority(Path::new("/in/urgent_e.csv")), 10, "the highest matching rule wins");
+        assert_eq!(drain(&queue), ["/in/urgent_c.txt", "/in/urgent_e.csv", "/in/b.csv", "/in/a.txt", "/in/d.txt"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn path_priorities_are_read_below_the_input_directory_only() {
+        let queue = queue(|config| config.path_priority_prefix = "priority-".to_string());
+        assert_eq!(queue.priority(Path::new("/in/priority-3/a.txt")), 3);
+        assert_eq!(queue.priority(Path::new("/in/priority-2/priority-7_b.txt")), 7);
+        assert_eq!(queue.priority(Path::new("/in/priority-x/c.txt")), 0);
+
+        let outside = JobQueue::new(&QueueConfig { path_priority_prefix: "priority-".to_string(), ..QueueConfig::default() }, Path::new("/data/priority-9/in"));
+        assert_eq!(outside.priority(Path::new("/data/priority-9/in/a.txt")), 0);
+        assert_eq!(outside.priority(Path::new("/data/priority-9/in/priority-1/a.txt")), 1);
+    }
+
+    #[test]
+    fn smallest_first_orders_by_size_within_a_priority() {
+        let dir = std::env::temp_dir().join(format!("cfp-queue-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut config = QueueConfig { ordering: QueueOrdering::SmallestFirst, ..QueueConfig::default() };
+        config.priority_rules = vec![PriorityRule { pattern: "urgent_*".to_string(), priority: 1 }];
+        let queue = JobQueue::new(&config, &dir);
+
+        for (name, size) in [("large", 30), ("small", 10), ("urgent_large", 20), ("medium", 20)] {
+            std::fs::write(dir.join(name), vec![b'x'; size]).unwrap();
+            queue.push(dir.join(name));
+        }
+        let order: Vec<PathBuf> = drain(&queue);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(order, ["urgent_large", "small", "medium", "large"].map(|name| dir.join(name)));
+    }
+
+    #[test]
+    fn duplicates_and_overflow_are_not_queued() {
+        let queue = queue(|config| {
+            config.capacity = 2;
+            config.high_water_mark = 1;
+        });
+        assert!(queue.push(PathBuf::from("/in/a")));
+        assert!(!queue.push(PathBuf::from("/in/a")));
+        assert!(queue.push(PathBuf::from("/in/b")));
+        assert!(!queue.push(PathBuf::from("/in/c")));
+        assert!(!queue.push(PathBuf::from("/in/d")));
+
+        let stats = queue.stats();
+        assert_eq!((stats.depth, stats.enqueued, stats.dropped, stats.spills), (2, 2, 2, 1));
+        assert!(queue.is_saturated());
+
+        // A popped path may be queued again
+        assert_eq!(drain(&queue).len(), 2);
+        assert!(queue.push(PathBuf::from("/in/a")));
+    }
+}
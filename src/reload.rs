@@ -0,0 +1,313 @@
+use crate::config::{AppConfig, flatten_table, to_toml_table};
+use crate::error::AppError;
+use crate::logging::{log_info, log_warning, log_error, log_debug};
+use crate::processor::SharedProcessor;
+use crate::queue::JobQueue;
+use crate::validation::ConfigIssue;
+
+use flexi_logger::{LogSpecification, LoggerHandle};
+use notify::event::{AccessKind, AccessMode};
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use tokio::time::{sleep, Duration};
+
+/// Fields applied to new jobs without restarting the service.
+/// Entries ending in `.` cover a whole section.
+const RELOADABLE_FIELDS: &[&str] = &[
+    "processing.",
+    "trigger.",
+    "logging.level",
+    "queue.ordering",
+    "queue.path_priority_prefix",
+    "queue.priority_rules",
+    "reload.debounce_ms",
+    "health.",
+    "webhook.",
+];
+
+/// A configuration field whose value differs between two configurations
+#[derive(Debug, Clone)]
+pub struct ConfigChange {
+    /// Dotted path of the field, e.g. `processing.strategy`
+    pub field: String,
+    pub old: Option<toml::Value>,
+    pub new: Option<toml::Value>,
+}
+
+impl ConfigChange {
+    /// Whether the change takes effect while the service is running
+    pub fn is_reloadable(&self) -> bool {
+        RELOADABLE_FIELDS.iter().any(|reloadable| match reloadable.strip_suffix('.') {
+            Some(section) => self.field.strip_prefix(section).is_some_and(|rest| rest.starts_with('.')),
+            None => self.field == *reloadable,
+        })
+    }
+
+    fn in_section(&self, section: &str) -> bool {
+        self.field.split('.').next() == Some(section)
+    }
+}
+
+impl std::fmt::Display for ConfigChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |value: &Option<toml::Value>| value.as_ref().map_or("(unset)".to_string(), ToString::to_string);
+        write!(f, "{}: {} -> {}", self.field, show(&self.old), show(&self.new))
+    }
+}
+
+/// List the fields that differ between two configurations
+pub fn diff_configs(old: &AppConfig, new: &AppConfig) -> Result<Vec<ConfigChange>, AppError> {
+    let mut old_values: BTreeMap<String, toml::Value> = flatten_table(&to_toml_table(old)?).into_iter().collect();
+    let new_values: BTreeMap<String, toml::Value> = flatten_table(&to_toml_table(new)?).into_iter().collect();
+
+    let mut changes = Vec::new();
+    for (field, new_value) in new_values {
+        let old_value = old_values.remove(&field);
+        if old_value.as_ref() != Some(&new_value) {
+            changes.push(ConfigChange { field, old: old_value, new: Some(new_value) });
+        }
+    }
+    for (field, old_value) in old_values {
+        changes.push(ConfigChange { field, old: Some(old_value), new: None });
+    }
+    changes.sort_by(|a, b| a.field.cmp(&b.field));
+    Ok(changes)
+}
+
+/// Watches the configuration file of the running service and applies reloadable
+/// changes to new jobs. Changes to other fields are logged as requiring a restart.
+pub struct ConfigReloader {
+    path: PathBuf,
+    /// Command-line overrides, applied again on every reload so they keep precedence
+    cli_overrides: Vec<(String, String)>,
+    /// Reject files with unknown fields instead of ignoring them
+    strict: bool,
+    logger: LoggerHandle,
+}
+
+impl ConfigReloader {
+    pub fn new(path: PathBuf, cli_overrides: Vec<(String, String)>, strict: bool, logger: LoggerHandle) -> Self {
+        Self { path, cli_overrides, strict, logger }
+    }
+
+    /// Watch the configuration file until the queue is closed.
+    /// `config` holds the configuration the service was started with and
+    /// receives every reloaded version.
+    pub async fn run(self, config: watch::Sender<AppConfig>, processor: SharedProcessor, queue: Arc<JobQueue>) {
+        let (tx_changed, mut rx_changed) = mpsc::channel(1);
+        let file_name = self.path.file_name().map(OsStr::to_os_string);
+
+        let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            match res {
+                Ok(event) if is_config_write(&event.kind) && event.paths.iter().any(|path| path.file_name() == file_name.as_deref()) => {
+                    // A pending signal already covers this change
+                    let _ = tx_changed.try_send(());
+                }
+                Ok(_) => {}
+                Err(e) => log_warning("Configuration watch error", &e.to_string()),
+            }
+        });
+
+        // Watch the directory rather than the file, since editors often replace the file on save
+        let watch_dir = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let _watcher = match watcher.and_then(|mut watcher| watcher.watch(&watch_dir, RecursiveMode::NonRecursive).map(|_| watcher)) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                log_error("Failed to watch configuration file, hot reload is disabled", &format!("{}: {}", self.path.display(), e));
+                return;
+            }
+        };
+
+        log_info("Watching configuration file for changes", &format!("{}", self.path.display()));
+
+        while rx_changed.recv().await.is_some() {
+            if queue.is_closed() {
+                break;
+            }
+            // Let the editor finish writing before reading the file
+            let debounce_ms = config.borrow().reload.debounce_ms;
+            sleep(Duration::from_millis(debounce_ms)).await;
+            while rx_changed.try_recv().is_ok() {}
+
+            let current = config.borrow().clone();
+            match self.reload(&current, &processor, &queue) {
+                Ok(Some(reloaded)) => {
+                    config.send_replace(reloaded);
+                }
+                Ok(None) => log_debug("Configuration file changed without effect", &format!("{}", self.path.display())),
+                Err(e) => log_error("Configuration reload failed, keeping the current settings", &e),
+            }
+        }
+    }
+
+    /// Load and validate the configuration again and apply the reloadable changes.
+    /// Returns the configuration now in effect, or `None` if no reloadable field changed.
+    fn reload(&self, current: &AppConfig, processor: &SharedProcessor, queue: &JobQueue) -> Result<Option<AppConfig>, AppError> {
+        let loaded = AppConfig::load_layered(Some(&self.path), &self.cli_overrides)?;
+        if self.strict {
+            loaded.deny_warnings()?;
+        }
+        let (errors, warnings): (Vec<_>, Vec<_>) = loaded.config.validate().into_iter().partition(ConfigIssue::is_error);
+        if !errors.is_empty() {
+            let details = errors.iter().map(ToString::to_string).collect::<Vec<_>>().join("; ");
+            return Err(AppError::config(format!("Invalid configuration: {}", details)));
+        }
+        for warning in &loaded.warnings {
+            log_warning("Configuration warning", warning);
+        }
+        for issue in &warnings {
+            log_warning("Configuration warning", &format!("{}: {}", issue.field, issue.message));
+        }
+
+        let changes = diff_configs(current, &loaded.config)?;
+        for change in changes.iter().filter(|change| !change.is_reloadable()) {
+            log_warning("Configuration change requires a restart", &change.to_string());
+        }
+        if !changes.iter().any(ConfigChange::is_reloadable) {
+            return Ok(None);
+        }
+        let config = effective_config(current, &changes)?;
+
+        let reloaded = |section: &str| changes.iter().any(|change| change.in_section(section) && change.is_reloadable());
+
+        // Prepare everything that can fail before applying anything
+        let new_processor = if reloaded("processing") || reloaded("trigger") || reloaded("webhook") {
+            Some(processor.current().reconfigure(config.processing.clone(), config.trigger.clone(), &config.webhook)?)
+        } else {
+            None
+        };
+        let new_log_spec = if reloaded("logging") {
+            Some(LogSpecification::parse(&config.logging.level).map_err(|e| {
+                AppError::Config { message: format!("Invalid log level '{}'", config.logging.level), source: Some(e.into()) }
+            })?)
+        } else {
+            None
+        };
+
+        if let Some(new_processor) = new_processor {
+            processor.replace(new_processor);
+        }
+        if reloaded("queue") {
+            queue.set_priorities(&config.queue);
+        }
+        if let Some(spec) = new_log_spec {
+            self.logger.set_new_spec(spec);
+        }
+
+        let applied: Vec<&ConfigChange> = changes.iter().filter(|change| change.is_reloadable()).collect();
+        for change in &applied {
+            log_info("Configuration changed", &change.to_string());
+        }
+        log_info("Configuration reloaded", &format!("{} ({} changed fields)", self.path.display(), applied.len()));
+        Ok(Some(config))
+    }
+}
+
+/// The configuration in effect after a reload: `current` with only the reloadable
+/// `changes` applied. Other changes wait for a restart, so they are left out.
+pub fn effective_config(current: &AppConfig, changes: &[ConfigChange]) -> Result<AppConfig, AppError> {
+    let mut table = to_toml_table(current)?;
+    for change in changes.iter().filter(|change| change.is_reloadable()) {
+        let mut segments: Vec<&str> = change.field.split('.').collect();
+        let field = segments.pop().unwrap_or_default();
+        let mut section = &mut table;
+        for segment in segments {
+            section = section
+                .entry(segment.to_string())
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| AppError::config(format!("{} is not a section", segment)))?;
+        }
+        match &change.new {
+            Some(value) => section.insert(field.to_string(), value.clone()),
+            None => section.remove(field),
+        };
+    }
+    toml::Value::Table(table).try_into().map_err(|e: toml::de::Error| {
+        AppError::Config { message: "Invalid configuration".to_string(), source: Some(e.into()) }
+    })
+}
+
+/// Whether a watch event may have changed a file's content
+fn is_config_write(kind: &EventKind) -> bool {
+    matches!(
+        kind,
+        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Access(AccessKind::Close(AccessMode::Write))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(field: &str) -> ConfigChange {
+        ConfigChange { field: field.to_string(), old: None, new: None }
+    }
+
+    #[test]
+    fn lists_changed_fields_sorted() {
+        let old = AppConfig::default();
+        let mut new = old.clone();
+        new.queue.workers = old.queue.workers + 1;
+        new.processing.strategy = "hash".to_string();
+
+        let changes = diff_configs(&old, &new).unwrap();
+        let fields: Vec<&str> = changes.iter().map(|change| change.field.as_str()).collect();
+        assert_eq!(fields, ["processing.strategy", "queue.workers"]);
+        assert_eq!(changes[0].new, Some(toml::Value::String("hash".to_string())));
+        assert_eq!(changes[1].old, Some(toml::Value::Integer(old.queue.workers as i64)));
+
+        assert!(diff_configs(&old, &old.clone()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn lists_are_compared_as_a_whole() {
+        let old = AppConfig::default();
+        let mut new = old.clone();
+        new.queue.priority_rules = vec![crate::config::PriorityRule { pattern: "urgent_*".to_string(), priority: 5 }];
+
+        let changes = diff_configs(&old, &new).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].field, "queue.priority_rules");
+        assert!(changes[0].is_reloadable());
+        assert_eq!(changes[0].to_string(), "queue.priority_rules: [] -> [{ pattern = \"urgent_*\", priority = 5 }]");
+    }
+
+    #[test]
+    fn only_reloadable_changes_take_effect() {
+        let current = AppConfig::default();
+        let mut loaded = current.clone();
+        loaded.processing.strategy = "hash".to_string();
+        loaded.queue.ordering = crate::config::QueueOrdering::SmallestFirst;
+        loaded.queue.workers = current.queue.workers + 1;
+        loaded.directories.input_dir = "/elsewhere".to_string();
+
+        let effective = effective_config(&current, &diff_configs(&current, &loaded).unwrap()).unwrap();
+        assert_eq!(effective.processing.strategy, "hash");
+        assert_eq!(effective.queue.ordering, crate::config::QueueOrdering::SmallestFirst);
+        assert_eq!(effective.queue.workers, current.queue.workers);
+        assert_eq!(effective.directories.input_dir, current.directories.input_dir);
+
+        // The restart-only changes are still reported on the next reload
+        let pending: Vec<String> = diff_configs(&effective, &loaded).unwrap().into_iter().map(|change| change.field).collect();
+        assert_eq!(pending, ["directories.input_dir", "queue.workers"]);
+    }
+
+    #[test]
+    fn reloadable_fields_match_whole_sections_or_exact_names() {
+        assert!(change("processing.strategy").is_reloadable());
+        assert!(change("webhook.targets").is_reloadable());
+        assert!(change("queue.ordering").is_reloadable());
+        assert!(!change("queue.workers").is_reloadable());
+        assert!(!change("directories.input_dir").is_reloadable());
+        assert!(!change("healthcheck.enabled").is_reloadable(), "a section prefix must end at a dot");
+        assert!(!change("logging.level_extra").is_reloadable());
+    }
+}
@@ -0,0 +1,194 @@
+use crate::error::AppError;
+use crate::job::JobId;
+use crate::logging::{log_debug, log_error};
+use crate::processor::ProcessingResult;
+use crate::utils::hash_file_content;
+
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+/// Extension appended to an output file's name for its sidecar
+pub const SIDECAR_EXTENSION: &str = ".json";
+
+/// Serializes appends to the manifest across workers
+static MANIFEST_LOCK: Mutex<()> = Mutex::const_new(());
+
+/// Machine-readable record of a processed file, written as a sidecar and as a manifest line
+#[derive(Debug, Clone, Serialize)]
+pub struct ResultRecord {
+    pub job_id: JobId,
+    pub input_file: PathBuf,
+    pub input_size: usize,
+    pub input_sha256: String,
+    pub outputs: Vec<OutputRecord>,
+    pub strategy: String,
+    pub strategy_info: Option<String>,
+    /// Job start and end in milliseconds since the Unix epoch
+    pub started_at_ms: u64,
+    pub finished_at_ms: u64,
+    /// Time spent in the processing strategy
+    pub processing_time_ms: u64,
+    /// Time from the start of the job until its output was written
+    pub total_time_ms: u64,
+}
+
+/// One file written by a job
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputRecord {
+    pub path: PathBuf,
+    pub size: usize,
+    pub sha256: String,
+}
+
+impl ResultRecord {
+    pub fn new(result: &ProcessingResult, input: &[u8], output: &[u8], finished_at_ms: u64) -> Self {
+        Self {
+            job_id: result.job_id,
+            input_file: result.input_file.clone(),
+            input_size: result.original_size,
+            input_sha256: hash_file_content(input),
+            outputs: vec![OutputRecord {
+                path: result.output_file.clone(),
+                size: result.processed_size,
+                sha256: hash_file_content(output),
+            }],
+            strategy: result.strategy.clone(),
+            strategy_info: result.strategy_info.clone(),
+            started_at_ms: result.started_at_ms,
+            finished_at_ms,
+            processing_time_ms: result.processing_time_ms,
+            total_time_ms: finished_at_ms.saturating_sub(result.started_at_ms),
+        }
+    }
+}
+
+/// Path of the sidecar written next to `output_file`
+pub fn sidecar_path(output_file: &Path) -> PathBuf {
+    let mut name = output_file.as_os_str().to_os_string();
+    name.push(SIDECAR_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Write `record` as pretty-printed JSON next to its first output file
+pub async fn write_sidecar(record: &ResultRecord) -> Result<PathBuf, AppError> {
+    let output_file = record.outputs.first().ok_or_else(|| AppError::File {
+        operation: "write the sidecar of",
+        path: record.input_file.clone(),
+        source: io::Error::new(io::ErrorKind::InvalidInput, "no output recorded"),
+    })?;
+    let path = sidecar_path(&output_file.path);
+    let mut json = serde_json::to_string_pretty(record).map_err(|e| AppError::file("write", &path, e.into()))?;
+    json.push('\n');
+
+    fs::write(&path, json).await.map_err(|e| {
+        log_error("Failed to write result sidecar", &format!("{}: {}", path.display(), e));
+        AppError::file("write", &path, e)
+    })?;
+    log_debug("Wrote result sidecar", &format!("{}", path.display()));
+    Ok(path)
+}
+
+/// Append `record` as one JSON line to the manifest at `path`, creating it if needed
+pub async fn append_to_manifest(record: &ResultRecord, path: &Path) -> Result<(), AppError> {
+    let mut line = serde_json::to_string(record).map_err(|e| AppError::file("append to", path, e.into()))?;
+    line.push('\n');
+
+    let _guard = MANIFEST_LOCK.lock().await;
+    let mut manifest = OpenOptions::new().create(true).append(true).open(path).await.map_err(|e| {
+        log_error("Failed to open result manifest", &format!("{}: {}", path.display(), e));
+        AppError::file("open", path, e)
+    })?;
+    manifest.write_all(line.as_bytes()).await.map_err(|e| {
+        log_error("Failed to append to result manifest", &format!("{}: {}", path.display(), e));
+        AppError::file("append to", path, e)
+    })?;
+    manifest.flush().await.map_err(|e| AppError::file("append to", path, e))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn record(output_file: &Path) -> ResultRecord {
+        let result = ProcessingResult {
+            job_id: JobId::new(),
+            input_file: PathBuf::from("input/a.txt"),
+            output_file: output_file.to_path_buf(),
+            original_size: 5,
+            processed_size: 5,
+            processing_time_ms: 3,
+            started_at_ms: 1_000,
+            strategy: "hash".to_string(),
+            strategy_info: None,
+        };
+        ResultRecord::new(&result, b"hello", b"HELLO", 1_010)
+    }
+
+    #[test]
+    fn sidecar_sits_next_to_the_output_file() {
+        assert_eq!(
+            sidecar_path(Path::new("out/a.processed.txt")),
+            PathBuf::from("out/a.processed.txt.json")
+        );
+        assert_eq!(sidecar_path(Path::new("out/noext")), PathBuf::from("out/noext.json"));
+    }
+
+    #[tokio::test]
+    async fn sidecar_contains_the_record() {
+        let dir = std::env::temp_dir().join(format!("cfp-results-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output_file = dir.join("a.processed.txt");
+        let record = record(&output_file);
+
+        let path = write_sidecar(&record).await.unwrap();
+        assert_eq!(path, dir.join("a.processed.txt.json"));
+
+        let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["job_id"], record.job_id.to_string());
+        assert_eq!(json["input_sha256"], hash_file_content(b"hello"));
+        assert_eq!(json["outputs"][0]["path"], output_file.display().to_string());
+        assert_eq!(json["outputs"][0]["sha256"], hash_file_content(b"HELLO"));
+        assert_eq!(json["total_time_ms"], 10);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_appends_write_one_line_per_job() {
+        let dir = std::env::temp_dir().join(format!("cfp-results-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let manifest = dir.join("manifest.jsonl");
+
+        let records: Vec<_> = (0..50).map(|i| record(&dir.join(format!("{i}.txt")))).collect();
+        let tasks: Vec<_> = records
+            .iter()
+            .cloned()
+            .map(|record| {
+                let manifest = manifest.clone();
+                tokio::spawn(async move { append_to_manifest(&record, &manifest).await })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap().unwrap();
+        }
+
+        let content = std::fs::read_to_string(&manifest).unwrap();
+        let ids: HashSet<String> = content
+            .lines()
+            .map(|line| {
+                let json: serde_json::Value = serde_json::from_str(line).unwrap();
+                json["job_id"].as_str().unwrap().to_string()
+            })
+            .collect();
+        assert_eq!(content.lines().count(), records.len());
+        assert_eq!(ids, records.iter().map(|r| r.job_id.to_string()).collect());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,193 @@
+use crate::config::ScriptConfig;
+use crate::error::{AppError, BoxError};
+use crate::logging::{log_debug, log_info};
+use crate::processor::ProcessingStrategy;
+
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+use std::path::PathBuf;
+
+/// Function every script has to define, called as `process(name, content)`
+pub const ENTRY_POINT: &str = "process";
+
+/// Processing strategy that runs a Rhai script for every file.
+///
+/// The script's `process(name, content)` receives the file name and its content as
+/// text, and returns either the output text or a map with `output` and optional
+/// `metadata`, which is reported as `strategy_info`. Scripts cannot access files,
+/// including other scripts through `import`, the network or the environment.
+pub struct ScriptStrategy {
+    path: PathBuf,
+    engine: Engine,
+    ast: AST,
+    config: ScriptConfig,
+}
+
+impl ScriptStrategy {
+    /// Compile the script at `config.path`
+    pub fn load(config: &ScriptConfig) -> Result<Self, AppError> {
+        if config.path.is_empty() {
+            return Err(AppError::config("processing.script.path must name the script to run"));
+        }
+        let path = PathBuf::from(&config.path);
+
+        let mut engine = Engine::new();
+        // The default resolver would let `import` load any script on disk
+        engine.set_module_resolver(DummyModuleResolver::new());
+        engine.set_max_operations(config.max_operations);
+        engine.set_max_string_size(usize::try_from(config.max_string_mb.saturating_mul(1024 * 1024)).unwrap_or(usize::MAX));
+        engine.set_max_array_size(config.max_collection_items);
+        engine.set_max_map_size(config.max_collection_items);
+        engine.set_max_call_levels(config.max_call_levels);
+        engine.on_print(|text| log_info("Script message", text));
+        engine.on_debug(|text, _, position| log_debug("Script debug message", &format!("{} ({})", text, position)));
+
+        let ast = engine.compile_file(path.clone())
+            .map_err(|e| AppError::Config { message: format!("Failed to compile script {}", path.display()), source: Some(e) })?;
+        if !ast.iter_functions().any(|function| function.name == ENTRY_POINT && function.params.len() == 2) {
+            return Err(AppError::config(format!(
+                "Script {} does not define fn {}(name, content)", path.display(), ENTRY_POINT
+            )));
+        }
+        log_debug("Loaded script", &format!("{}", path.display()));
+        Ok(Self { path, engine, ast, config: config.clone() })
+    }
+
+    /// Split what `process` returned into the output and the information reported about the run
+    fn into_output(result: Dynamic) -> Result<(String, Option<String>), String> {
+        if result.is_string() {
+            return Ok((result.into_string()?, None));
+        }
+        let type_name = result.type_name();
+        let Some(mut map) = result.try_cast::<Map>() else {
+            return Err(format!("returned {}, expected a string or a map with 'output'", type_name));
+        };
+        let output = map.remove("output")
+            .ok_or_else(|| "returned a map without 'output'".to_string())?
+            .into_string()
+            .map_err(|type_name| format!("returned {} as 'output', expected a string", type_name))?;
+        let info = match map.remove("metadata") {
+            None => None,
+            Some(metadata) if metadata.is_unit() => None,
+            Some(metadata) if metadata.is_string() => Some(metadata.into_string()?),
+            Some(metadata) => Some(serde_json::to_string(&metadata).map_err(|e| format!("returned metadata that cannot be serialized: {}", e))?),
+        };
+        Ok((output, info))
+    }
+}
+
+impl ProcessingStrategy for ScriptStrategy {
+    fn name(&self) -> &str {
+        "script"
+    }
+
+    fn process_content(&self, file_name: &str, content: &[u8]) -> Result<String, AppError> {
+        self.process_with_info(file_name, content).map(|(output, _)| output)
+    }
+
+    fn process_with_info(&self, file_name: &str, content: &[u8]) -> Result<(String, Option<String>), AppError> {
+        let failed = |message: String, source: Option<BoxError>| AppError::Strategy {
+            strategy: self.name().to_string(),
+            file: file_name.to_string(),
+            message: format!("script {} {}", self.path.display(), message),
+            source,
+        };
+        let limit_exceeded = |limit: String| AppError::LimitExceeded { strategy: self.name().to_string(), file: file_name.to_string(), limit };
+
+        let text = String::from_utf8_lossy(content).into_owned();
+        let result = self.engine
+            .call_fn::<Dynamic>(&mut Scope::new(), &self.ast, ENTRY_POINT, (file_name.to_string(), text))
+            .map_err(|e| match *e {
+                EvalAltResult::ErrorTooManyOperations(_) => {
+                    limit_exceeded(format!("processing.script.max_operations ({})", self.config.max_operations))
+                }
+                EvalAltResult::ErrorDataTooLarge(ref what, _) if what.starts_with("Length of string") => {
+                    limit_exceeded(format!("processing.script.max_string_mb ({} MB)", self.config.max_string_mb))
+                }
+                EvalAltResult::ErrorDataTooLarge(..) => {
+                    limit_exceeded(format!("processing.script.max_collection_items ({})", self.config.max_collection_items))
+                }
+                EvalAltResult::ErrorStackOverflow(_) => {
+                    limit_exceeded(format!("processing.script.max_call_levels ({})", self.config.max_call_levels))
+                }
+                // `throw` in the script
+                EvalAltResult::ErrorRuntime(ref reason, _) => AppError::Rejected {
+                    strategy: self.name().to_string(),
+                    file: file_name.to_string(),
+                    reason: reason.to_string(),
+                },
+                _ => failed("failed".to_string(), Some(e)),
+            })?;
+        Self::into_output(result).map_err(|message| failed(message, None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Load `source` as a script with the default limits, adjusted by `configure`
+    fn load(source: &str, configure: impl FnOnce(&mut ScriptConfig)) -> Result<ScriptStrategy, AppError> {
+        let path = std::env::temp_dir().join(format!("cfp-script-{}.rhai", uuid::Uuid::new_v4()));
+        std::fs::write(&path, source).unwrap();
+        let mut config = ScriptConfig { path: path.to_string_lossy().into_owned(), ..ScriptConfig::default() };
+        configure(&mut config);
+        let strategy = ScriptStrategy::load(&config);
+        std::fs::remove_file(&path).unwrap();
+        strategy
+    }
+
+    fn run(source: &str, configure: impl FnOnce(&mut ScriptConfig)) -> Result<(String, Option<String>), AppError> {
+        load(source, configure)?.process_with_info("a.txt", b"hello")
+    }
+
+    #[test]
+    fn returns_text_or_output_with_metadata() {
+        let (output, info) = run("fn process(name, content) { content.to_upper() }", |_| {}).unwrap();
+        assert_eq!((output.as_str(), info), ("HELLO", None));
+
+        let (output, info) = run("fn process(name, content) { #{ output: name, metadata: #{ length: content.len() } } }", |_| {}).unwrap();
+        assert_eq!(output, "a.txt");
+        assert_eq!(info.as_deref(), Some(r#"{"length":5}"#));
+    }
+
+    #[test]
+    fn requires_the_entry_point() {
+        assert!(matches!(load("fn handle(name, content) { content }", |_| {}), Err(AppError::Config { .. })));
+        assert!(matches!(load("fn process(content) { content }", |_| {}), Err(AppError::Config { .. })));
+    }
+
+    #[test]
+    fn throw_rejects_the_file() {
+        let error = run(r#"fn process(name, content) { throw "bad header" }"#, |_| {}).unwrap_err();
+        assert!(matches!(error, AppError::Rejected { ref reason, .. } if reason == "bad header"), "{}", error);
+    }
+
+    #[test]
+    fn imports_are_not_resolved() {
+        let error = run(r#"fn process(name, content) { import "other" as other; content }"#, |_| {}).unwrap_err();
+        assert!(matches!(error, AppError::Strategy { .. }), "{}", error);
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let limit = |error: AppError| match error {
+            AppError::LimitExceeded { limit, .. } => limit,
+            other => panic!("expected a limit error, got {}", other),
+        };
+
+        let error = run("fn process(name, content) { loop { } }", |config| config.max_operations = 1000).unwrap_err();
+        assert!(limit(error).starts_with("processing.script.max_operations"));
+
+        let error = run("fn f(x) { f(x + 1) } fn process(name, content) { f(0) }", |_| {}).unwrap_err();
+        assert!(limit(error).starts_with("processing.script.max_call_levels"));
+
+        let source = "fn process(name, content) { let items = []; for i in 0..100 { items.push(i); } content }";
+        let error = run(source, |config| config.max_collection_items = 10).unwrap_err();
+        assert!(limit(error).starts_with("processing.script.max_collection_items"));
+
+        let source = "fn process(name, content) { let text = content; for i in 0..30 { text += text; } text }";
+        let error = run(source, |config| config.max_string_mb = 1).unwrap_err();
+        assert!(limit(error).starts_with("processing.script.max_string_mb"));
+    }
+}
@@ -0,0 +1,122 @@
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::http::{self, HttpState};
+use crate::logging::{log_info, log_info_simple};
+use crate::processor::{FileProcessor, SharedProcessor};
+use crate::queue::{JobQueue, log_queue_stats};
+use crate::reload::ConfigReloader;
+use crate::utils::setup_directories;
+use crate::webhook::wait_for_deliveries;
+use crate::watcher::{supervise_watcher, rescan_periodically, rescan_on_spill, enqueue_existing_files};
+
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::time::{interval, Duration};
+
+/// Run the long-running service: watch the input directory and process new
+/// files from a bounded job queue until the queue is closed. With a reloader,
+/// changes to the configuration file are applied while the service runs.
+pub async fn run_service(config: &AppConfig, reloader: Option<ConfigReloader>) -> Result<(), AppError> {
+    log_info_simple("Starting Concurrent File Processor service...");
+
+    let input_dir = config.input_dir();
+    let output_dir = config.output_dir();
+
+    setup_directories(&input_dir, &output_dir).await?;
+
+    // Job queue between file detection and the processing workers.
+    let queue = Arc::new(JobQueue::new(&config.queue, &input_dir));
+
+    // Create file processor with configuration; a config reload swaps it for new jobs
+    let processor = SharedProcessor::new(FileProcessor::new(config.processing.clone())?
+        .with_trigger(config.trigger.clone())
+        .with_webhooks(&config.webhook)?);
+
+    // Effective configuration, updated on every reload
+    let (config_tx, config_rx) = watch::channel(config.clone());
+
+    // Bind before doing any work so a port conflict fails startup
+    if config.http.enabled {
+        let listener = http::bind(&config.http).await?;
+        let http_config = config.http.clone();
+        let state = HttpState {
+            queue: Arc::clone(&queue),
+            processor: processor.clone(),
+            config: config_rx.clone(),
+            input_dir: input_dir.clone(),
+            output_dir: output_dir.clone(),
+            recursive: config.watcher.recursive,
+        };
+        tokio::spawn(async move {
+            http::run_http_server(listener, http_config, state).await;
+        });
+    }
+
+    log_info_simple("Processing existing files in input directory...");
+    let existing = enqueue_existing_files(&input_dir, &queue, config.watcher.recursive).await?;
+    log_info("Found existing files to process", &format!("{} files", existing));
+
+    let watcher_input_dir = input_dir.clone();
+    let watcher_queue = Arc::clone(&queue);
+    let watcher_config = config.watcher.clone();
+    tokio::spawn(async move {
+        log_info_simple("Starting file watcher...");
+        supervise_watcher(&watcher_input_dir, watcher_queue, &watcher_config).await;
+    });
+
+    // --- Reconciliation Rescan Tasks ---
+    let spill_input_dir = input_dir.clone();
+    let spill_queue = Arc::clone(&queue);
+    let spill_config = config.watcher.clone();
+    tokio::spawn(async move {
+        rescan_on_spill(&spill_input_dir, spill_queue, &spill_config).await;
+    });
+
+    if config.watcher.rescan_interval_sec > 0 {
+        let rescan_input_dir = input_dir.clone();
+        let rescan_queue = Arc::clone(&queue);
+        let rescan_config = config.watcher.clone();
+        tokio::spawn(async move {
+            rescan_periodically(&rescan_input_dir, rescan_queue, &rescan_config).await;
+        });
+    }
+
+    if config.queue.stats_log_interval_sec > 0 {
+        let stats_queue = Arc::clone(&queue);
+        let mut stats_interval = interval(Duration::from_secs(config.queue.stats_log_interval_sec));
+        tokio::spawn(async move {
+            stats_interval.tick().await;
+            while !stats_queue.is_closed() {
+                stats_interval.tick().await;
+                log_queue_stats(&stats_queue.stats());
+            }
+        });
+    }
+
+    // --- Configuration Hot Reload ---
+    if let Some(reloader) = reloader {
+        let reload_processor = processor.clone();
+        let reload_queue = Arc::clone(&queue);
+        tokio::spawn(async move {
+            reloader.run(config_tx, reload_processor, reload_queue).await;
+        });
+    }
+
+    // --- File Processor Workers ---
+    log_info("Starting file processing workers", &format!("{} workers", config.queue.workers));
+    let mut workers = Vec::with_capacity(config.queue.workers);
+    for _ in 0..config.queue.workers {
+        let worker_processor = processor.clone();
+        let worker_queue = Arc::clone(&queue);
+        let worker_output_dir = output_dir.clone();
+        workers.push(tokio::spawn(async move {
+            worker_processor.run_worker(worker_queue, worker_output_dir).await;
+        }));
+    }
+    futures::future::join_all(workers).await;
+    let shutdown_timeout = config_rx.borrow().webhook.shutdown_timeout_sec;
+    wait_for_deliveries(Duration::from_secs(shutdown_timeout)).await;
+
+    log_info_simple("Concurrent File Processor service stopped gracefully.");
+    Ok(())
+}
@@ -0,0 +1,176 @@
+use crate::config::{TriggerConfig, TriggerMode};
+use crate::logging::{log_info, log_debug, log_warning};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Files that are ready to be processed together, and the marker that released them
+#[derive(Debug)]
+pub struct ReadySet {
+    pub files: Vec<PathBuf>,
+    pub marker: Option<PathBuf>,
+}
+
+/// Decide which files a detected path makes ready for processing.
+/// Returns `None` if nothing is ready yet (e.g. a data file whose marker has not
+/// been written) or the path should be ignored.
+pub fn resolve_ready_files(path: &Path, config: &TriggerConfig) -> Option<ReadySet> {
+    if config.mode == TriggerMode::Immediate {
+        return Some(ReadySet { files: vec![path.to_path_buf()], marker: None });
+    }
+
+    let file_name = path.file_name()?.to_string_lossy().into_owned();
+
+    if file_name == config.batch_marker {
+        let dir = path.parent()?;
+        let files = list_batch_files(dir, config);
+        log_info("Batch marker found", &format!("{} releases {} files", path.display(), files.len()));
+        return Some(ReadySet { files, marker: Some(path.to_path_buf()) });
+    }
+
+    if let Some(data_name) = file_name.strip_suffix(&config.marker_suffix) {
+        let data_path = path.with_file_name(data_name);
+        if data_path.is_file() {
+            return Some(ReadySet { files: vec![data_path], marker: Some(path.to_path_buf()) });
+        }
+        log_warning("Marker found without data file", &format!("{}", path.display()));
+        return None;
+    }
+
+    let marker_path = path.with_file_name(format!("{}{}", file_name, config.marker_suffix));
+    if marker_path.is_file() {
+        return Some(ReadySet { files: vec![path.to_path_buf()], marker: Some(marker_path) });
+    }
+
+    log_debug("File is not ready yet, waiting for marker", &format!("{}", path.display()));
+    None
+}
+
+/// List the files released by a batch marker in `dir`, skipping marker files
+fn list_batch_files(dir: &Path, config: &TriggerConfig) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            log_warning("Failed to list batch directory", &format!("{}: {}", dir.display(), e));
+            return Vec::new();
+        }
+    };
+
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            name != config.batch_marker && !name.ends_with(&config.marker_suffix)
+        })
+        .collect()
+}
+
+/// A marker file shared by the jobs it released.
+/// The marker is consumed once every job finished successfully; if any job failed
+/// it is kept so that a later rescan releases the remaining files again.
+#[derive(Debug)]
+pub struct ReadyMarker {
+    path: PathBuf,
+    remaining: AtomicUsize,
+    failed: AtomicBool,
+}
+
+impl ReadyMarker {
+    pub fn new(path: PathBuf, jobs: usize) -> Self {
+        Self { path, remaining: AtomicUsize::new(jobs), failed: AtomicBool::new(false) }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record that one of the released jobs finished
+    pub async fn complete(&self, success: bool) {
+        if !success {
+            self.failed.store(true, Ordering::SeqCst);
+        }
+        if self.remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.consume().await;
+        }
+    }
+
+    /// Remove the marker unless one of its jobs failed
+    pub async fn consume(&self) {
+        if self.failed.load(Ordering::SeqCst) {
+            log_warning("Keeping marker because a released file failed", &format!("{}", self.path.display()));
+            return;
+        }
+        match tokio::fs::remove_file(&self.path).await {
+            Ok(()) => log_info("Marker consumed", &format!("{}", self.path.display())),
+            // Another release of the same marker may already have consumed it
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log_warning("Failed to remove marker", &format!("{}: {}", self.path.display(), e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn marker_config() -> TriggerConfig {
+        TriggerConfig { mode: TriggerMode::Marker, ..TriggerConfig::default() }
+    }
+
+    /// A fresh directory containing empty files with the given names
+    fn dir_with(names: &[&str]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cfp-trigger-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in names {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn immediate_mode_releases_the_path_itself() {
+        let path = Path::new("/in/a.csv");
+        let ready = resolve_ready_files(path, &TriggerConfig::default()).unwrap();
+        assert_eq!(ready.files, [path]);
+        assert_eq!(ready.marker, None);
+    }
+
+    #[test]
+    fn data_file_waits_for_its_marker() {
+        let dir = dir_with(&["a.csv", "b.csv", "b.csv.done"]);
+        let config = marker_config();
+
+        assert!(resolve_ready_files(&dir.join("a.csv"), &config).is_none());
+
+        let ready = resolve_ready_files(&dir.join("b.csv"), &config).unwrap();
+        assert_eq!(ready.files, [dir.join("b.csv")]);
+        assert_eq!(ready.marker, Some(dir.join("b.csv.done")));
+
+        // The marker releases its data file the same way
+        let ready = resolve_ready_files(&dir.join("b.csv.done"), &config).unwrap();
+        assert_eq!(ready.files, [dir.join("b.csv")]);
+        assert_eq!(ready.marker, Some(dir.join("b.csv.done")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn marker_without_data_file_releases_nothing() {
+        let dir = dir_with(&["gone.csv.done"]);
+        assert!(resolve_ready_files(&dir.join("gone.csv.done"), &marker_config()).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn batch_marker_releases_every_data_file_in_its_directory() {
+        let dir = dir_with(&["a.csv", "b.csv", "c.csv.done", "_SUCCESS"]);
+        fs::create_dir(dir.join("nested")).unwrap();
+
+        let mut ready = resolve_ready_files(&dir.join("_SUCCESS"), &marker_config()).unwrap();
+        ready.files.sort();
+        assert_eq!(ready.files, [dir.join("a.csv"), dir.join("b.csv")]);
+        assert_eq!(ready.marker, Some(dir.join("_SUCCESS")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,8 +1,10 @@
 use crate::error::AppError;
-use crate::logging::{log_info, log_error, log_error_simple, log_debug};
+use crate::logging::{log_info, log_error, log_error_simple, log_debug, log_fields};
 use crate::config::ProcessingConfig;
+use crate::metrics::metrics;
+use log::kv::Value;
+use log::Level;
 use sha2::{Sha256, Digest};
-use hex;
 
 use tokio::io::AsyncWriteExt;
 use tokio::{
@@ -11,7 +13,9 @@ use tokio::{
     time::{sleep, Duration},
 };
 
+use std::io;
 use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 // File size formatting constants (these are not configuration, they're just formatting helpers)
 const KB: u64 = 1024;
@@ -26,6 +30,11 @@ pub fn hash_file_content(content: &[u8]) -> String {
     hex::encode(result)
 }
 
+/// Milliseconds since the Unix epoch, as used in job records
+pub fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_millis() as u64).unwrap_or(0)
+}
+
 /// Check if a file is available for processing (not locked by another process)
 pub async fn is_file_available(file_path: &Path, config: &ProcessingConfig) -> bool {
     match File::open(file_path).await {
@@ -53,9 +62,18 @@ pub async fn wait_for_file_availability(
         if is_file_available(file_path, config).await {
             return Ok(());
         }
+        if !file_path.exists() {
+            log_error("File disappeared before it could be processed", &format!("{}", file_path.display()));
+            return Err(AppError::NotFound { path: file_path.to_path_buf() });
+        }
         
-        log_info("File not available, retrying", &format!("{} (attempt {}/{})", 
-            file_path.display(), retry_count + 1, config.max_retries));
+        log_fields(Level::Info, "File not available, retrying", &format!("{} (attempt {}/{})", 
+            file_path.display(), retry_count + 1, config.max_retries), &[
+            ("file", Value::from_display(&file_path.display())),
+            ("attempt", Value::from(retry_count + 1)),
+            ("max_attempts", Value::from(config.max_retries)),
+        ]);
+        metrics().record_file_retry();
         
         sleep(delay).await;
         retry_count += 1;
@@ -65,20 +83,20 @@ pub async fn wait_for_file_availability(
     }
     
     log_error("File still not available after retries", &format!("{}", file_path.display()));
-    Err(AppError::Processing(format!("File {} is not available after retries", file_path.display())))
+    Err(AppError::FileLocked { path: file_path.to_path_buf(), attempts: config.max_retries })
 }
 
 /// Read file content asynchronously
 pub async fn read_file_content(file_path: &Path) -> Result<Vec<u8>, AppError> {
     let mut file = File::open(file_path).await.map_err(|e| {
         log_error("Failed to open file", &format!("{}: {}", file_path.display(), e));
-        AppError::Io(e)
+        AppError::file("open", file_path, e)
     })?;
     
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer).await.map_err(|e| {
         log_error("Failed to read file", &format!("{}: {}", file_path.display(), e));
-        AppError::Io(e)
+        AppError::file("read", file_path, e)
     })?;
     
     log_info("Read bytes from file", &format!("{} bytes from {}", buffer.len(), file_path.display()));
@@ -89,14 +107,26 @@ pub async fn read_file_content(file_path: &Path) -> Result<Vec<u8>, AppError> {
 pub fn extract_file_name(file_path: &Path) -> Result<String, AppError> {
     Ok(file_path.file_name()
         .ok_or_else(|| {
-            let msg = format!("Could not get file name for {}", file_path.display());
-            log_error_simple(&msg);
-            AppError::Processing(msg)
+            log_error_simple(&format!("Could not get file name for {}", file_path.display()));
+            AppError::File {
+                operation: "process",
+                path: file_path.to_path_buf(),
+                source: io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"),
+            }
         })?
         .to_string_lossy()
         .into_owned())
 }
 
+/// Path of the output file written for an input file name
+pub fn output_path_for(file_name: &str, output_dir: &Path, output_extension: &str) -> PathBuf {
+    let output_file_name = format!("{}{}", 
+        Path::new(file_name).file_stem().unwrap().to_string_lossy(),
+        output_extension
+    );
+    output_dir.join(output_file_name)
+}
+
 /// Write processed data to output file
 pub async fn write_processed_data(
     file_name: &str, 
@@ -104,22 +134,18 @@ pub async fn write_processed_data(
     output_dir: &Path,
     output_extension: &str
 ) -> Result<PathBuf, AppError> {
-    let output_file_name = format!("{}{}", 
-        Path::new(file_name).file_stem().unwrap().to_string_lossy(),
-        output_extension
-    );
-    let output_path = output_dir.join(&output_file_name);
+    let output_path = output_path_for(file_name, output_dir, output_extension);
     
     log_info("Writing processed output to", &format!("{}", output_path.display()));
 
     let mut output_file = File::create(&output_path).await.map_err(|e| {
         log_error("Failed to create output file", &format!("{}: {}", output_path.display(), e));
-        AppError::Io(e)
+        AppError::file("create", &output_path, e)
     })?;
     
     output_file.write_all(processed_data.as_bytes()).await.map_err(|e| {
         log_error("Failed to write to output file", &format!("{}: {}", output_path.display(), e));
-        AppError::Io(e)
+        AppError::file("write", &output_path, e)
     })?;
     
     log_info("Wrote processed data to", &format!("{}", output_path.display()));
@@ -130,31 +156,37 @@ pub async fn write_processed_data(
 pub async fn remove_original_file(file_path: &Path) -> Result<(), AppError> {
     fs::remove_file(file_path).await.map_err(|e| {
         log_error("Failed to remove original file", &format!("{}: {}", file_path.display(), e));
-        AppError::Io(e)
+        AppError::file("remove", file_path, e)
     })?;
     
     log_info("Original file removed", &format!("{}", file_path.display()));
     Ok(())
 }
 
-/// Scan input directory for files to process
-pub async fn scan_input_directory(input_dir: &Path) -> Result<Vec<PathBuf>, AppError> {
+/// Scan input directory for files to process, descending into subdirectories if `recursive`
+pub async fn scan_input_directory(input_dir: &Path, recursive: bool) -> Result<Vec<PathBuf>, AppError> {
     let mut existing_files = Vec::new();
-    let mut read_dir = fs::read_dir(input_dir).await.map_err(|e| {
-        log_error("Failed to read input directory for initial scan", &e);
-        AppError::Io(e)
-    })?;
-    
-    while let Some(entry) = read_dir.next_entry().await.map_err(|e| {
-        log_error("Failed to read directory entry during initial scan", &e);
-        AppError::Io(e)
-    })? {
-        let path = entry.path();
-        if path.is_file() {
-            existing_files.push(path);
+    let mut pending_dirs = vec![input_dir.to_path_buf()];
+
+    while let Some(dir) = pending_dirs.pop() {
+        let mut read_dir = fs::read_dir(&dir).await.map_err(|e| {
+            log_error("Failed to read input directory for initial scan", &e);
+            AppError::file("read directory", &dir, e)
+        })?;
+
+        while let Some(entry) = read_dir.next_entry().await.map_err(|e| {
+            log_error("Failed to read directory entry during initial scan", &e);
+            AppError::file("read directory", &dir, e)
+        })? {
+            let path = entry.path();
+            if path.is_file() {
+                existing_files.push(path);
+            } else if recursive && path.is_dir() {
+                pending_dirs.push(path);
+            }
         }
     }
-    
+
     Ok(existing_files)
 }
 
@@ -164,7 +196,7 @@ pub async fn ensure_output_directory(output_dir: &Path) -> Result<(), AppError>
         log_info("Creating output directory", &format!("{}", output_dir.display()));
         fs::create_dir_all(output_dir).await.map_err(|e| {
             log_error("Failed to create output directory", &format!("{}: {}", output_dir.display(), e));
-            AppError::Io(e)
+            AppError::file("create directory", output_dir, e)
         })?;
     }
     Ok(())
@@ -173,11 +205,15 @@ pub async fn ensure_output_directory(output_dir: &Path) -> Result<(), AppError>
 /// Validate file path and check if it's a regular file
 pub fn validate_file_path(file_path: &Path) -> Result<(), AppError> {
     if !file_path.exists() {
-        return Err(AppError::Processing(format!("File does not exist: {}", file_path.display())));
+        return Err(AppError::NotFound { path: file_path.to_path_buf() });
     }
     
     if !file_path.is_file() {
-        return Err(AppError::Processing(format!("Path is not a file: {}", file_path.display())));
+        return Err(AppError::File {
+            operation: "process",
+            path: file_path.to_path_buf(),
+            source: io::Error::new(io::ErrorKind::InvalidInput, "not a regular file"),
+        });
     }
     
     Ok(())
@@ -187,7 +223,7 @@ pub fn validate_file_path(file_path: &Path) -> Result<(), AppError> {
 pub async fn get_file_size(file_path: &Path) -> Result<u64, AppError> {
     let metadata = fs::metadata(file_path).await.map_err(|e| {
         log_error("Failed to get file metadata", &format!("{}: {}", file_path.display(), e));
-        AppError::Io(e)
+        AppError::file("read metadata of", file_path, e)
     })?;
     
     Ok(metadata.len())
@@ -204,11 +240,11 @@ pub fn format_file_size(bytes: u64) -> String {
 }
 
 /// Setup input and output directories, creating them if they don't exist
-pub async fn setup_directories(input_dir: &PathBuf, output_dir: &PathBuf) -> Result<(), AppError> {
+pub async fn setup_directories(input_dir: &Path, output_dir: &Path) -> Result<(), AppError> {
     // Ensure input directory exists
     fs::create_dir_all(input_dir).await.map_err(|e| {
         log_error("Failed to create input directory", &e);
-        AppError::Io(e)
+        AppError::file("create directory", input_dir, e)
     })?;
     
     // Ensure output directory exists
@@ -0,0 +1,551 @@
+use crate::config::{AppConfig, RotationAge, TriggerMode, WatcherBackend};
+use crate::command::{parse_env_entry, templated_shell_script};
+use crate::processor::{strategy_from_config, STRATEGY_NAMES};
+use crate::script::ScriptStrategy;
+
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+
+/// How serious a configuration problem is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The service cannot run correctly with this setting
+    Error,
+    /// The setting is accepted but probably not what was intended
+    Warning,
+}
+
+/// A problem with a single configuration field
+#[derive(Debug, Clone)]
+pub struct ConfigIssue {
+    pub severity: Severity,
+    /// Dotted path of the field, e.g. `queue.workers`
+    pub field: String,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn error(field: &str, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, field: field.to_string(), message: message.into() }
+    }
+
+    fn warning(field: &str, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, field: field.to_string(), message: message.into() }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}: {}", severity, self.field, self.message)
+    }
+}
+
+impl AppConfig {
+    /// Check the configuration for values that parse but cannot work, such as
+    /// zero-sized buffers or an output directory inside the input directory.
+    pub fn validate(&self) -> Vec<ConfigIssue> {
+        let mut issues = Vec::new();
+        self.validate_processing(&mut issues);
+        self.validate_directories(&mut issues);
+        self.validate_logging(&mut issues);
+        self.validate_watcher(&mut issues);
+        self.validate_trigger(&mut issues);
+        self.validate_queue(&mut issues);
+        self.validate_http(&mut issues);
+        self.validate_webhook(&mut issues);
+        issues
+    }
+
+    fn validate_processing(&self, issues: &mut Vec<ConfigIssue>) {
+        let processing = &self.processing;
+        if processing.max_retries == 0 {
+            issues.push(ConfigIssue::error("processing.max_retries", "must be at least 1, otherwise no file is ever opened"));
+        }
+        if processing.initial_retry_delay_ms == 0 {
+            issues.push(ConfigIssue::warning("processing.initial_retry_delay_ms", "0 retries locked files without waiting"));
+        }
+        if processing.max_retry_delay_sec.saturating_mul(1000) < processing.initial_retry_delay_ms {
+            issues.push(ConfigIssue::warning("processing.max_retry_delay_sec", format!(
+                "{}s is shorter than initial_retry_delay_ms ({}ms)",
+                processing.max_retry_delay_sec, processing.initial_retry_delay_ms
+            )));
+        }
+        if processing.output_extension.is_empty() {
+            issues.push(ConfigIssue::warning("processing.output_extension", "is empty, output files only differ from inputs by losing their extension"));
+        }
+        if processing.output_extension.contains(['/', '\\']) {
+            issues.push(ConfigIssue::error("processing.output_extension", "must not contain path separators"));
+        }
+        if processing.write_manifest && (processing.manifest_file.is_empty() || processing.manifest_file.contains(['/', '\\'])) {
+            issues.push(ConfigIssue::error("processing.manifest_file", "must be a file name without path separators"));
+        }
+        if processing.strategy == "command" {
+            self.validate_command(issues);
+        } else if processing.strategy == "script" {
+            self.validate_script(issues);
+        } else if !STRATEGY_NAMES.contains(&processing.strategy.as_str()) {
+            self.validate_plugin(issues);
+        }
+    }
+
+    fn validate_script(&self, issues: &mut Vec<ConfigIssue>) {
+        let script = &self.processing.script;
+        if script.path.is_empty() {
+            issues.push(ConfigIssue::error("processing.script.path", "must name the script to run when strategy is \"script\""));
+            return;
+        }
+        if script.max_operations == 0 {
+            issues.push(ConfigIssue::warning("processing.script.max_operations", "0 lets a script that never returns block a worker forever"));
+        }
+        // Compiling the script here reports syntax errors before the service starts
+        if let Err(e) = ScriptStrategy::load(script) {
+            issues.push(ConfigIssue::error("processing.script.path", format!("{:#}", e)));
+        }
+    }
+
+    fn validate_plugin(&self, issues: &mut Vec<ConfigIssue>) {
+        let wasm = &self.processing.wasm;
+        if wasm.max_memory_mb == 0 {
+            issues.push(ConfigIssue::error("processing.wasm.max_memory_mb", "must be at least 1"));
+            return;
+        }
+        if wasm.fuel == 0 {
+            issues.push(ConfigIssue::warning("processing.wasm.fuel", "0 lets a plugin that never returns block a worker forever"));
+        }
+        // Loading compiles the module, so broken plugins are reported before the service starts
+        if let Err(e) = strategy_from_config(&self.processing) {
+            issues.push(ConfigIssue::error("processing.strategy", format!("{:#}", e)));
+        }
+    }
+
+    fn validate_command(&self, issues: &mut Vec<ConfigIssue>) {
+        let command = &self.processing.command;
+        if command.argv.first().is_none_or(|program| program.is_empty()) {
+            issues.push(ConfigIssue::error("processing.command.argv", "must name the program to run when strategy is \"command\""));
+        }
+        for entry in &command.env {
+            if parse_env_entry(entry).is_none() {
+                issues.push(ConfigIssue::error("processing.command.env", format!("'{}' is not of the form KEY=VALUE", entry)));
+            }
+        }
+        if !command.working_dir.is_empty() && !Path::new(&command.working_dir).is_dir() {
+            issues.push(ConfigIssue::warning("processing.command.working_dir", format!("{} is not a directory", command.working_dir)));
+        }
+        if let Some(script) = templated_shell_script(&command.argv) {
+            issues.push(ConfigIssue::error("processing.command.argv", format!(
+                "placeholders in the shell script '{}' let file names inject shell code; use \"$CFP_INPUT\", \"$CFP_OUTPUT\" and \"$CFP_NAME\" instead",
+                script
+            )));
+        }
+        for code in command.retry_codes.iter().chain(&command.reject_codes) {
+            if command.success_codes.contains(code) {
+                issues.push(ConfigIssue::warning("processing.command.success_codes", format!("exit code {} is also a retry or reject code and counts as success", code)));
+            }
+        }
+        if command.timeout_sec == 0 {
+            issues.push(ConfigIssue::warning("processing.command.timeout_sec", "0 lets a hanging command block a worker forever"));
+        }
+        if command.success_codes.is_empty() {
+            issues.push(ConfigIssue::error("processing.command.success_codes", "must contain at least one exit code"));
+        }
+        if command.max_output_mb == 0 {
+            issues.push(ConfigIssue::error("processing.command.max_output_mb", "must be at least 1"));
+        }
+        if cfg!(not(unix)) && (command.max_memory_mb > 0 || command.max_cpu_sec > 0) {
+            issues.push(ConfigIssue::warning("processing.command", "max_memory_mb and max_cpu_sec are only enforced on Unix"));
+        }
+    }
+
+    fn validate_directories(&self, issues: &mut Vec<ConfigIssue>) {
+        let directories = &self.directories;
+        let input_empty = directories.input_dir.trim().is_empty();
+        let output_empty = directories.output_dir.trim().is_empty();
+        if input_empty {
+            issues.push(ConfigIssue::error("directories.input_dir", "must not be empty"));
+        }
+        if output_empty {
+            issues.push(ConfigIssue::error("directories.output_dir", "must not be empty"));
+        }
+        if input_empty || output_empty {
+            return;
+        }
+
+        let input_dir = normalize_path(&self.input_dir());
+        let output_dir = normalize_path(&self.output_dir());
+        if input_dir == output_dir {
+            issues.push(ConfigIssue::error("directories.output_dir", format!(
+                "is the same directory as input_dir ({}), outputs would be processed again",
+                input_dir.display()
+            )));
+        } else if output_dir.starts_with(&input_dir) {
+            if self.watcher.recursive {
+                issues.push(ConfigIssue::error("directories.output_dir", format!(
+                    "is inside input_dir ({}) and the watcher is recursive, outputs would be processed again",
+                    input_dir.display()
+                )));
+            } else {
+                issues.push(ConfigIssue::warning("directories.output_dir", format!(
+                    "is inside input_dir ({}); this only works while watcher.recursive is false",
+                    input_dir.display()
+                )));
+            }
+        }
+    }
+
+    fn validate_logging(&self, issues: &mut Vec<ConfigIssue>) {
+        let logging = &self.logging;
+        // A bare word is a global level; `module=level` entries are checked by the spec parser
+        let bad_level = logging.level.split(',')
+            .map(str::trim)
+            .find(|part| !part.is_empty() && !part.contains('=') && part.parse::<log::LevelFilter>().is_err());
+        if let Some(part) = bad_level {
+            issues.push(ConfigIssue::error("logging.level", format!("'{}' is not a log level (trace, debug, info, warn, error, off)", part)));
+        } else if let Err(e) = flexi_logger::LogSpecification::parse(&logging.level) {
+            issues.push(ConfigIssue::error("logging.level", format!("'{}' is not a valid log specification: {}", logging.level, e)));
+        }
+        if logging.log_dir.trim().is_empty() {
+            issues.push(ConfigIssue::error("logging.log_dir", "must not be empty"));
+        }
+        if logging.log_basename.trim().is_empty() {
+            issues.push(ConfigIssue::error("logging.log_basename", "must not be empty"));
+        }
+        if logging.rotate_size_mb == 0 && logging.rotate_age == RotationAge::Never && logging.compress {
+            issues.push(ConfigIssue::warning("logging.compress", "has no effect while rotation is disabled (rotate_size_mb = 0, rotate_age = \"never\")"));
+        }
+        if logging.rotate_size_mb > u64::MAX / (1024 * 1024) {
+            issues.push(ConfigIssue::error("logging.rotate_size_mb", "is too large"));
+        }
+    }
+
+    fn validate_watcher(&self, issues: &mut Vec<ConfigIssue>) {
+        let watcher = &self.watcher;
+        if watcher.channel_buffer_size == 0 {
+            issues.push(ConfigIssue::error("watcher.channel_buffer_size", "must be at least 1"));
+        }
+        if watcher.restart_initial_delay_ms == 0 {
+            issues.push(ConfigIssue::warning("watcher.restart_initial_delay_ms", "0 re-creates a failing watcher in a tight loop"));
+        }
+        if watcher.restart_max_delay_sec.saturating_mul(1000) < watcher.restart_initial_delay_ms {
+            issues.push(ConfigIssue::warning("watcher.restart_max_delay_sec", format!(
+                "{}s is shorter than restart_initial_delay_ms ({}ms)",
+                watcher.restart_max_delay_sec, watcher.restart_initial_delay_ms
+            )));
+        }
+        let may_poll = watcher.backend == WatcherBackend::Poll || watcher.fallback_to_poll;
+        if may_poll && watcher.poll_interval_ms == 0 {
+            issues.push(ConfigIssue::error("watcher.poll_interval_ms", "must be at least 1 when polling can be used"));
+        }
+        if watcher.rescan_interval_sec == 0 {
+            issues.push(ConfigIssue::warning("watcher.rescan_interval_sec", "periodic rescans are disabled, files with missed events wait for the next restart"));
+        }
+    }
+
+    fn validate_trigger(&self, issues: &mut Vec<ConfigIssue>) {
+        let trigger = &self.trigger;
+        if trigger.mode != TriggerMode::Marker {
+            return;
+        }
+        if trigger.marker_suffix.is_empty() {
+            issues.push(ConfigIssue::error("trigger.marker_suffix", "must not be empty in marker mode"));
+        }
+        if trigger.batch_marker.is_empty() {
+            issues.push(ConfigIssue::error("trigger.batch_marker", "must not be empty in marker mode"));
+        }
+        if trigger.batch_marker.contains(['/', '\\']) {
+            issues.push(ConfigIssue::error("trigger.batch_marker", "must be a file name, not a path"));
+        }
+        if trigger.batch_marker.ends_with(&trigger.marker_suffix) && !trigger.marker_suffix.is_empty() {
+            issues.push(ConfigIssue::warning("trigger.batch_marker", "ends with marker_suffix, so it is also treated as a per-file marker"));
+        }
+    }
+
+    fn validate_queue(&self, issues: &mut Vec<ConfigIssue>) {
+        let queue = &self.queue;
+        if queue.capacity == 0 {
+            issues.push(ConfigIssue::error("queue.capacity", "must be at least 1"));
+        }
+        if queue.workers == 0 {
+            issues.push(ConfigIssue::error("queue.workers", "must be at least 1, otherwise no file is processed"));
+        }
+        if queue.high_water_mark == 0 {
+            issues.push(ConfigIssue::error("queue.high_water_mark", "must be at least 1"));
+        } else if queue.high_water_mark > queue.capacity {
+            issues.push(ConfigIssue::warning("queue.high_water_mark", format!(
+                "{} is above capacity ({}) and is never reached",
+                queue.high_water_mark, queue.capacity
+            )));
+        }
+        for (index, rule) in queue.priority_rules.iter().enumerate() {
+            if let Err(e) = glob::Pattern::new(&rule.pattern) {
+                issues.push(ConfigIssue::error(&format!("queue.priority_rules[{}].pattern", index), format!("'{}' is not a valid glob: {}", rule.pattern, e)));
+            }
+        }
+    }
+
+    fn validate_http(&self, issues: &mut Vec<ConfigIssue>) {
+        let http = &self.http;
+        if !http.enabled {
+            return;
+        }
+        if http.listen.parse::<SocketAddr>().is_err() {
+            issues.push(ConfigIssue::error("http.listen", format!("'{}' is not a valid address, expected e.g. 127.0.0.1:9898", http.listen)));
+        }
+        if http.admin && http.admin_token.is_empty() {
+            issues.push(ConfigIssue::error("http.admin_token", "must be set when admin is true, otherwise any local process or web page could control the service"));
+        }
+    }
+
+    fn validate_webhook(&self, issues: &mut Vec<ConfigIssue>) {
+        let webhook = &self.webhook;
+        if webhook.targets.is_empty() {
+            return;
+        }
+        if webhook.max_attempts == 0 {
+            issues.push(ConfigIssue::error("webhook.max_attempts", "must be at least 1, otherwise no webhook is sent"));
+        }
+        if webhook.timeout_ms == 0 {
+            issues.push(ConfigIssue::error("webhook.timeout_ms", "must be at least 1"));
+        }
+        if webhook.max_retry_delay_ms < webhook.retry_delay_ms {
+            issues.push(ConfigIssue::warning("webhook.max_retry_delay_ms", format!(
+                "{}ms is shorter than retry_delay_ms ({}ms)",
+                webhook.max_retry_delay_ms, webhook.retry_delay_ms
+            )));
+        }
+        for (index, target) in webhook.targets.iter().enumerate() {
+            if !target.url.starts_with("http://") && !target.url.starts_with("https://") {
+                issues.push(ConfigIssue::error(&format!("webhook.targets[{}].url", index), format!("'{}' must start with http:// or https://", target.url)));
+            } else if target.url.starts_with("http://") && !target.secret.is_empty() {
+                issues.push(ConfigIssue::warning(&format!("webhook.targets[{}].url", index), "sends its signed payloads unencrypted; use https://"));
+            }
+            if target.events.is_empty() {
+                issues.push(ConfigIssue::warning(&format!("webhook.targets[{}].events", index), "is empty, so nothing is sent to this target"));
+            }
+            for pattern in &target.files {
+                if let Err(e) = glob::Pattern::new(pattern) {
+                    issues.push(ConfigIssue::error(&format!("webhook.targets[{}].files", index), format!("'{}' is not a valid glob: {}", pattern, e)));
+                }
+            }
+        }
+    }
+}
+
+/// Make a path absolute and resolve `.` and `..` so paths can be compared.
+/// Existing paths are canonicalized to also resolve symlinks.
+fn normalize_path(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return canonical;
+    }
+
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{PriorityRule, WebhookEvent, WebhookTarget};
+
+    fn severity_of(config: &AppConfig, field: &str) -> Option<Severity> {
+        config.validate().into_iter().find(|issue| issue.field == field).map(|issue| issue.severity)
+    }
+
+    fn webhook_target(url: &str, secret: &str) -> WebhookTarget {
+        WebhookTarget {
+            url: url.to_string(),
+            secret: secret.to_string(),
+            events: vec![WebhookEvent::Completed, WebhookEvent::Failed],
+            files: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn default_config_has_no_errors() {
+        let issues = AppConfig::default().validate();
+        assert!(!issues.iter().any(ConfigIssue::is_error), "{:?}", issues);
+    }
+
+    #[test]
+    fn retry_delays_shorter_than_the_initial_delay_are_warned_about() {
+        let mut config = AppConfig::default();
+        config.processing.initial_retry_delay_ms = 5000;
+        config.processing.max_retry_delay_sec = 2;
+        assert_eq!(severity_of(&config, "processing.max_retry_delay_sec"), Some(Severity::Warning));
+
+        config.watcher.restart_initial_delay_ms = 5000;
+        config.watcher.restart_max_delay_sec = 2;
+        assert_eq!(severity_of(&config, "watcher.restart_max_delay_sec"), Some(Severity::Warning));
+    }
+
+    #[test]
+    fn huge_max_delays_do_not_overflow() {
+        let mut config = AppConfig::default();
+        config.processing.max_retry_delay_sec = u64::MAX;
+        config.watcher.restart_max_delay_sec = u64::MAX;
+        assert_eq!(severity_of(&config, "processing.max_retry_delay_sec"), None);
+        assert_eq!(severity_of(&config, "watcher.restart_max_delay_sec"), None);
+    }
+
+    #[test]
+    fn zero_sizes_are_errors() {
+        let mut config = AppConfig::default();
+        config.processing.max_retries = 0;
+        config.queue.workers = 0;
+        config.queue.capacity = 0;
+        config.watcher.channel_buffer_size = 0;
+        for field in ["processing.max_retries", "queue.workers", "queue.capacity", "watcher.channel_buffer_size"] {
+            assert_eq!(severity_of(&config, field), Some(Severity::Error), "{}", field);
+        }
+    }
+
+    #[test]
+    fn output_extension_must_not_be_a_path() {
+        let mut config = AppConfig::default();
+        config.processing.output_extension = "/../out.txt".to_string();
+        assert_eq!(severity_of(&config, "processing.output_extension"), Some(Severity::Error));
+    }
+
+    #[test]
+    fn output_directory_must_not_be_the_input_directory() {
+        let mut config = AppConfig::default();
+        config.directories.input_dir = "./no-such-dir/in".to_string();
+        config.directories.output_dir = "./no-such-dir/other/../in".to_string();
+        assert_eq!(severity_of(&config, "directories.output_dir"), Some(Severity::Error));
+    }
+
+    #[test]
+    fn output_directory_inside_the_input_directory_depends_on_recursion() {
+        let mut config = AppConfig::default();
+        config.directories.input_dir = "./no-such-dir/in".to_string();
+        config.directories.output_dir = "./no-such-dir/in/out".to_string();
+        config.watcher.recursive = false;
+        assert_eq!(severity_of(&config, "directories.output_dir"), Some(Severity::Warning));
+        config.watcher.recursive = true;
+        assert_eq!(severity_of(&config, "directories.output_dir"), Some(Severity::Error));
+    }
+
+    #[test]
+    fn log_levels_are_checked() {
+        let mut config = AppConfig::default();
+        config.logging.level = "info, concurrent_file_processor=debug".to_string();
+        assert_eq!(severity_of(&config, "logging.level"), None);
+        config.logging.level = "verbose".to_string();
+        assert_eq!(severity_of(&config, "logging.level"), Some(Severity::Error));
+    }
+
+    #[test]
+    fn marker_settings_are_only_checked_in_marker_mode() {
+        let mut config = AppConfig::default();
+        config.trigger.marker_suffix = String::new();
+        assert_eq!(severity_of(&config, "trigger.marker_suffix"), None);
+        config.trigger.mode = TriggerMode::Marker;
+        assert_eq!(severity_of(&config, "trigger.marker_suffix"), Some(Severity::Error));
+    }
+
+    #[test]
+    fn invalid_priority_patterns_name_their_rule() {
+        let mut config = AppConfig::default();
+        config.queue.priority_rules = vec![
+            PriorityRule { pattern: "*.csv".to_string(), priority: 1 },
+            PriorityRule { pattern: "[".to_string(), priority: 2 },
+        ];
+        assert_eq!(severity_of(&config, "queue.priority_rules[0].pattern"), None);
+        assert_eq!(severity_of(&config, "queue.priority_rules[1].pattern"), Some(Severity::Error));
+    }
+
+    #[test]
+    fn http_listen_address_is_only_checked_when_enabled() {
+        let mut config = AppConfig::default();
+        config.http.listen = "localhost".to_string();
+        assert_eq!(severity_of(&config, "http.listen"), None);
+        config.http.enabled = true;
+        assert_eq!(severity_of(&config, "http.listen"), Some(Severity::Error));
+    }
+
+    #[test]
+    fn admin_api_requires_a_token() {
+        let mut config = AppConfig::default();
+        config.http.enabled = true;
+        config.http.admin = true;
+        assert_eq!(severity_of(&config, "http.admin_token"), Some(Severity::Error));
+        config.http.admin_token = "s3cret".to_string();
+        assert_eq!(severity_of(&config, "http.admin_token"), None);
+    }
+
+    #[test]
+    fn webhook_targets_are_checked() {
+        let mut config = AppConfig::default();
+        config.webhook.targets = vec![
+            webhook_target("ftp://example.com/hook", ""),
+            webhook_target("http://example.com/hook", "s3cret"),
+            webhook_target("https://example.com/hook", "s3cret"),
+        ];
+        assert_eq!(severity_of(&config, "webhook.targets[0].url"), Some(Severity::Error));
+        assert_eq!(severity_of(&config, "webhook.targets[1].url"), Some(Severity::Warning));
+        assert_eq!(severity_of(&config, "webhook.targets[2].url"), None);
+        assert_eq!(severity_of(&config, "webhook.max_retry_delay_ms"), None);
+
+        config.webhook.max_retry_delay_ms = 10;
+        assert_eq!(severity_of(&config, "webhook.max_retry_delay_ms"), Some(Severity::Warning));
+    }
+
+    #[test]
+    fn command_strategy_needs_a_program_and_valid_environment() {
+        let mut config = AppConfig::default();
+        config.processing.strategy = "command".to_string();
+        config.processing.command.env = vec!["LANG=C".to_string(), "=oops".to_string()];
+        assert_eq!(severity_of(&config, "processing.command.argv"), Some(Severity::Error));
+        let env_errors = config.validate().into_iter().filter(|issue| issue.field == "processing.command.env").count();
+        assert_eq!(env_errors, 1);
+    }
+
+    #[test]
+    fn placeholders_inside_shell_scripts_are_errors() {
+        let mut config = AppConfig::default();
+        config.processing.strategy = "command".to_string();
+        let argv = |args: &[&str]| args.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        config.processing.command.argv = argv(&["/bin/sh", "-c", "cat {input}"]);
+        assert_eq!(severity_of(&config, "processing.command.argv"), Some(Severity::Error));
+        config.processing.command.argv = argv(&["bash", "-ec", "echo {name}"]);
+        assert_eq!(severity_of(&config, "processing.command.argv"), Some(Severity::Error));
+
+        config.processing.command.argv = argv(&["sh", "-c", "cat \"$CFP_INPUT\""]);
+        assert_eq!(severity_of(&config, "processing.command.argv"), None);
+        config.processing.command.argv = argv(&["sh", "-c", "gzip -c \"$1\"", "sh", "{input}"]);
+        assert_eq!(severity_of(&config, "processing.command.argv"), None);
+        config.processing.command.argv = argv(&["convert", "{input}", "-c", "{output}"]);
+        assert_eq!(severity_of(&config, "processing.command.argv"), None);
+    }
+
+    #[test]
+    fn unknown_strategies_are_errors() {
+        let mut config = AppConfig::default();
+        config.processing.strategy = "no_such_strategy".to_string();
+        config.processing.wasm.plugins_dir = "./no-such-dir".to_string();
+        assert_eq!(severity_of(&config, "processing.strategy"), Some(Severity::Error));
+    }
+}
@@ -0,0 +1,300 @@
+use crate::config::WasmConfig;
+use crate::error::AppError;
+use crate::logging::{log_debug, log_info};
+use crate::processor::ProcessingStrategy;
+
+use std::path::{Path, PathBuf};
+use wasmi::{Caller, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TrapCode, TypedFunc};
+
+/// Extension of plugin modules in the plugin directory
+pub const PLUGIN_EXTENSION: &str = "wasm";
+/// Module name of the functions the host offers to plugins
+pub const HOST_MODULE: &str = "cfp";
+
+/// Per-file state of a plugin instance
+struct PluginState {
+    limits: StoreLimits,
+    /// Message passed to `cfp.fail`, if the plugin rejected the file
+    failure: Option<String>,
+}
+
+/// Exports every plugin has to provide
+struct PluginExports {
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    process: TypedFunc<(i32, i32, i32, i32), i64>,
+}
+
+/// Processing strategy backed by a WebAssembly module from the plugin directory.
+///
+/// Every file is processed in a fresh instance, limited by `processing.wasm.fuel`
+/// and `processing.wasm.max_memory_mb`. The module has no access to the file system,
+/// the network or the clock; it can only use the `cfp` host functions.
+pub struct WasmStrategy {
+    name: String,
+    module: Module,
+    linker: Linker<PluginState>,
+    fuel: u64,
+    max_memory: usize,
+}
+
+impl WasmStrategy {
+    /// Compile the plugin `name` and check that it can be instantiated
+    pub fn load(name: &str, config: &WasmConfig) -> Result<Self, AppError> {
+        let path = plugin_path(name, config)
+            .ok_or_else(|| AppError::config(format!("No plugin named '{}' in {}", name, config.plugins_dir)))?;
+        let invalid = |e: wasmi::Error| AppError::Config {
+            message: format!("Invalid plugin {}", path.display()),
+            source: Some(e.into()),
+        };
+
+        let bytes = std::fs::read(&path).map_err(|e| AppError::file("read", &path, e))?;
+        let mut engine_config = wasmi::Config::default();
+        engine_config.consume_fuel(config.fuel > 0);
+        let engine = Engine::new(&engine_config);
+        let module = Module::new(&engine, bytes).map_err(invalid)?;
+
+        let mut linker = Linker::new(&engine);
+        linker.func_wrap(HOST_MODULE, "log", |caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+            log_info("Plugin message", &read_guest_string(&caller, ptr, len)?);
+            Ok(())
+        }).map_err(|e| invalid(e.into()))?;
+        linker.func_wrap(HOST_MODULE, "fail", |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| {
+            let message = read_guest_string(&caller, ptr, len)?;
+            caller.data_mut().failure = Some(message.clone());
+            Err::<(), _>(wasmi::Error::new(message))
+        }).map_err(|e| invalid(e.into()))?;
+
+        let strategy = Self {
+            name: name.to_string(),
+            module,
+            linker,
+            fuel: config.fuel,
+            max_memory: usize::try_from(config.max_memory_mb.saturating_mul(1024 * 1024)).unwrap_or(usize::MAX),
+        };
+        let mut store = strategy.new_store();
+        strategy.instantiate(&mut store).map_err(invalid)?;
+        log_debug("Loaded plugin", &format!("{} from {}", name, path.display()));
+        Ok(strategy)
+    }
+
+    fn new_store(&self) -> Store<PluginState> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.max_memory)
+            .trap_on_grow_failure(true)
+            .build();
+        let mut store = Store::new(self.module.engine(), PluginState { limits, failure: None });
+        store.limiter(|state| &mut state.limits);
+        if self.fuel > 0 {
+            store.set_fuel(self.fuel).expect("fuel metering is enabled when fuel is set");
+        }
+        store
+    }
+
+    fn instantiate(&self, store: &mut Store<PluginState>) -> Result<PluginExports, wasmi::Error> {
+        let instance = self.linker.instantiate_and_start(&mut *store, &self.module)?;
+        let memory = instance.get_memory(&*store, "memory")
+            .ok_or_else(|| wasmi::Error::new("plugin does not export its memory as 'memory'"))?;
+        let alloc = instance.get_typed_func(&*store, "alloc")
+            .map_err(|e| wasmi::Error::new(format!("export 'alloc' (i32) -> i32: {}", e)))?;
+        let process = instance.get_typed_func(&*store, "process")
+            .map_err(|e| wasmi::Error::new(format!("export 'process' (i32, i32, i32, i32) -> i64: {}", e)))?;
+        Ok(PluginExports { memory, alloc, process })
+    }
+
+    /// Describe why a call into the plugin failed
+    fn call_failed(&self, store: &mut Store<PluginState>, file_name: &str, error: wasmi::Error) -> AppError {
+        if let Some(reason) = store.data_mut().failure.take() {
+            return AppError::Rejected { strategy: self.name.clone(), file: file_name.to_string(), reason };
+        }
+        let limit = match error.as_trap_code() {
+            Some(TrapCode::OutOfFuel) => format!("processing.wasm.fuel ({})", self.fuel),
+            Some(TrapCode::GrowthOperationLimited) => format!("processing.wasm.max_memory_mb ({} MB)", self.max_memory / (1024 * 1024)),
+            _ => {
+                return AppError::Strategy {
+                    strategy: self.name.clone(),
+                    file: file_name.to_string(),
+                    message: "plugin call failed".to_string(),
+                    source: Some(error.into()),
+                };
+            }
+        };
+        AppError::LimitExceeded { strategy: self.name.clone(), file: file_name.to_string(), limit }
+    }
+}
+
+impl ProcessingStrategy for WasmStrategy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn process_content(&self, file_name: &str, content: &[u8]) -> Result<String, AppError> {
+        let mut store = self.new_store();
+        let exports = self.instantiate(&mut store).map_err(|e| self.call_failed(&mut store, file_name, e))?;
+
+        let (name_ptr, name_len) = copy_to_guest(&mut store, &exports, file_name.as_bytes())
+            .map_err(|e| self.call_failed(&mut store, file_name, e))?;
+        let (data_ptr, data_len) = copy_to_guest(&mut store, &exports, content)
+            .map_err(|e| self.call_failed(&mut store, file_name, e))?;
+        let packed = exports.process.call(&mut store, (name_ptr, name_len, data_ptr, data_len))
+            .map_err(|e| self.call_failed(&mut store, file_name, e))?;
+
+        // The result is the output's address in the high and its length in the low 32 bits
+        let (output_ptr, output_len) = ((packed as u64 >> 32) as usize, (packed as u64 & 0xffff_ffff) as usize);
+        let bad_output = |message: &str| AppError::Strategy {
+            strategy: self.name.clone(),
+            file: file_name.to_string(),
+            message: message.to_string(),
+            source: None,
+        };
+        let output = output_ptr.checked_add(output_len)
+            .and_then(|output_end| exports.memory.data(&store).get(output_ptr..output_end))
+            .ok_or_else(|| bad_output("returned output outside its memory"))?;
+        String::from_utf8(output.to_vec())
+            .map_err(|_| bad_output("returned output that is not UTF-8 text"))
+    }
+}
+
+/// Path of the plugin `name`, if it is a plain name and the module exists
+pub fn plugin_path(name: &str, config: &WasmConfig) -> Option<PathBuf> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return None;
+    }
+    let path = Path::new(&config.plugins_dir).join(format!("{}.{}", name, PLUGIN_EXTENSION));
+    path.is_file().then_some(path)
+}
+
+/// Names of the plugins in the plugin directory, sorted
+pub fn plugin_names(config: &WasmConfig) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(&config.plugins_dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension == PLUGIN_EXTENSION))
+        .filter_map(|path| path.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Copy `bytes` into memory allocated with the plugin's `alloc`
+fn copy_to_guest(store: &mut Store<PluginState>, exports: &PluginExports, bytes: &[u8]) -> Result<(i32, i32), wasmi::Error> {
+    let len = i32::try_from(bytes.len()).map_err(|_| wasmi::Error::new("input is too large for a plugin"))?;
+    let ptr = exports.alloc.call(&mut *store, len)?;
+    exports.memory.write(&mut *store, ptr as u32 as usize, bytes)
+        .map_err(|e| wasmi::Error::new(format!("'alloc' returned unusable memory: {}", e)))?;
+    Ok((ptr, len))
+}
+
+/// Read a UTF-8 string the plugin passed to a host function
+fn read_guest_string(caller: &Caller<'_, PluginState>, ptr: i32, len: i32) -> Result<String, wasmi::Error> {
+    let memory = caller.get_export("memory").and_then(Extern::into_memory)
+        .ok_or_else(|| wasmi::Error::new("plugin does not export its memory as 'memory'"))?;
+    let (start, len) = (ptr as u32 as usize, len as u32 as usize);
+    let bytes = start.checked_add(len)
+        .and_then(|end| memory.data(caller).get(start..end))
+        .ok_or_else(|| wasmi::Error::new("string passed to the host is outside the plugin's memory"))?;
+    Ok(String::from_utf8_lossy(bytes).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Host imports and a bump allocator shared by the test plugins; `$process` is appended
+    const PRELUDE: &str = r#"
+        (import "cfp" "fail" (func $fail (param i32 i32)))
+        (memory (export "memory") 1)
+        (global $heap (mut i32) (i32.const 1024))
+        (data (i32.const 0) "bad header")
+        (func (export "alloc") (param $len i32) (result i32)
+          (global.get $heap)
+          (global.set $heap (i32.add (global.get $heap) (local.get $len))))
+    "#;
+
+    /// Compile a plugin whose `process` export has the given body and load it with `configure`d limits
+    fn load(process_body: &str, configure: impl FnOnce(&mut WasmConfig)) -> WasmStrategy {
+        let dir = std::env::temp_dir().join(format!("cfp-plugins-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = format!(
+            "(module {} (func (export \"process\") (param i32 i32 i32 i32) (result i64) {}))",
+            PRELUDE, process_body
+        );
+        std::fs::write(dir.join("test.wasm"), wat::parse_str(source).unwrap()).unwrap();
+
+        let mut config = WasmConfig { plugins_dir: dir.to_string_lossy().into_owned(), ..WasmConfig::default() };
+        configure(&mut config);
+        let strategy = WasmStrategy::load("test", &config);
+        std::fs::remove_dir_all(&dir).unwrap();
+        strategy.unwrap()
+    }
+
+    fn run(process_body: &str, configure: impl FnOnce(&mut WasmConfig)) -> Result<String, AppError> {
+        load(process_body, configure).process_content("a.txt", b"hello")
+    }
+
+    fn limit(error: AppError) -> String {
+        match error {
+            AppError::LimitExceeded { limit, .. } => limit,
+            other => panic!("expected a limit error, got {}", other),
+        }
+    }
+
+    #[test]
+    fn returns_the_output_the_plugin_points_to() {
+        // The content is copied right after the file name, at 1024 + 5
+        let output = run("(i64.or (i64.shl (i64.extend_i32_u (local.get 2)) (i64.const 32)) (i64.extend_i32_u (local.get 3)))", |_| {});
+        assert_eq!(output.unwrap(), "hello");
+    }
+
+    #[test]
+    fn an_endless_loop_runs_out_of_fuel() {
+        let error = run("(loop $forever (br $forever)) (i64.const 0)", |config| config.fuel = 100_000).unwrap_err();
+        assert_eq!(limit(error), "processing.wasm.fuel (100000)");
+    }
+
+    #[test]
+    fn memory_cannot_grow_past_the_limit() {
+        let body = "(drop (memory.grow (i32.const 32))) (i64.const 0)";
+        let error = run(body, |config| config.max_memory_mb = 1).unwrap_err();
+        assert_eq!(limit(error), "processing.wasm.max_memory_mb (1 MB)");
+        assert!(run(body, |config| config.max_memory_mb = 4).is_ok());
+    }
+
+    #[test]
+    fn fail_rejects_the_file() {
+        let error = run("(call $fail (i32.const 0) (i32.const 10)) (i64.const 0)", |_| {}).unwrap_err();
+        assert!(matches!(error, AppError::Rejected { ref reason, .. } if reason == "bad header"), "{}", error);
+        assert!(!error.is_retryable());
+
+        // A string outside the plugin's memory, even one whose end overflows, is a trap
+        let error = run("(call $fail (i32.const -16) (i32.const 32)) (i64.const 0)", |_| {}).unwrap_err();
+        assert!(matches!(error, AppError::Strategy { .. }), "{}", error);
+    }
+
+    #[test]
+    fn output_outside_the_memory_is_rejected() {
+        for packed in ["0x0000_ffff_0000_0100", "0xffff_fff0_0000_0100", "0xffff_ffff_ffff_ffff"] {
+            let error = run(&format!("(i64.const {})", packed), |_| {}).unwrap_err();
+            assert!(error.to_string().contains("returned output outside its memory"), "{}: {}", packed, error);
+        }
+    }
+
+    #[test]
+    fn the_example_plugin_upper_cases_text() {
+        let dir = std::env::temp_dir().join(format!("cfp-plugins-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = include_str!("../examples/plugins/uppercase.wat");
+        std::fs::write(dir.join("uppercase.wasm"), wat::parse_str(source).unwrap()).unwrap();
+        let config = WasmConfig { plugins_dir: dir.to_string_lossy().into_owned(), ..WasmConfig::default() };
+
+        assert_eq!(plugin_names(&config), ["uppercase"]);
+        assert!(plugin_path("../uppercase", &config).is_none());
+        let strategy = WasmStrategy::load("uppercase", &config).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(strategy.process_content("a.txt", b"Hello, World").unwrap(), "HELLO, WORLD");
+        assert!(matches!(strategy.process_content("a.txt", b""), Err(AppError::Rejected { .. })));
+    }
+}
@@ -1,57 +1,61 @@
 use crate::error::AppError;
-use crate::logging::{log_info, log_error, log_debug};
-use crate::config::WatcherConfig;
+use crate::logging::{log_info, log_error, log_debug, log_warning, log_fields};
+use crate::config::{WatcherConfig, WatcherBackend};
+use crate::utils::scan_input_directory;
+use crate::queue::JobQueue;
+use crate::metrics::{metrics, DetectionSource};
+use crate::health::{set_watcher_state, WatcherState};
 
+use log::kv::Value;
+use log::Level;
 use std::{
-    path::{Path, PathBuf},
+    path::Path,
+    sync::Arc,
 };
 
 use tokio::{
-    sync::mpsc,
-    time::{sleep, Duration},
+    sync::mpsc::{self, error::TrySendError},
+    time::{sleep, Duration, Instant},
 };
 
-pub async fn watch_files(path: &Path, tx: mpsc::Sender<PathBuf>, config: &WatcherConfig) -> Result<(), AppError> {
-    use notify::{recommended_watcher, EventKind, Watcher};
+type NotifyResult = Result<notify::Event, notify::Error>;
 
-    let (tx_notify, mut rx_notify) = mpsc::channel(config.channel_buffer_size);
-    let mut watcher = recommended_watcher(move |res: Result<notify::Event, notify::Error>| {
-        if let Err(e) = tx_notify.blocking_send(res) {
-            log_error("Failed to send notify event", &e);
-        }
-    }).map_err(|e| {
-        log_error("Failed to create file watcher", &e);
-        AppError::Watch(e)
-    })?;
+pub async fn watch_files(path: &Path, queue: Arc<JobQueue>, config: &WatcherConfig) -> Result<(), AppError> {
+    use notify::EventKind;
 
-    let recursive_mode = if config.recursive {
-        notify::RecursiveMode::Recursive
-    } else {
-        notify::RecursiveMode::NonRecursive
-    };
+    let (tx_notify, mut rx_notify) = mpsc::channel(config.channel_buffer_size);
+    // Keep the watcher alive for as long as events are being received
+    let _watcher = start_watcher(path, tx_notify, Arc::clone(&queue), config)?;
+    set_watcher_state(WatcherState::Running, None);
 
-    watcher.watch(path, recursive_mode).map_err(|e| {
-        log_error("Failed to watch directory", &format!("{}: {}", path.display(), e));
-        AppError::Watch(e)
-    })?;
-    
     log_info("Watching directory", &format!("{}", path.display()));
 
     while let Some(res) = rx_notify.recv().await {
+        if queue.is_closed() {
+            break;
+        }
         match res {
             Ok(event) => {
                 log_debug("Received watch event", &format!("{:?}", event));
+                if event.need_rescan() {
+                    // The backend dropped events (e.g. inotify queue overflow); the
+                    // supervisor rescans the directory and re-creates the watcher.
+                    log_warning("Watcher reported missed events", &format!("{}", path.display()));
+                    return Err(AppError::Watch(notify::Error::generic("missed events, a rescan is required").add_path(path.to_path_buf())));
+                }
                 if let EventKind::Create(..) = event.kind {
                     for path in event.paths {
                         if path.is_file() {
-                            log_info("New file detected", &format!("{}", path.display()));
-                            
+                            log_fields(Level::Info, "New file detected", &format!("{}", path.display()), &[
+                                ("file", Value::from_display(&path.display())),
+                            ]);
+
                             // Add a small delay to allow file system operations to complete
                             sleep(Duration::from_millis(config.processing_delay_ms)).await;
-                            
-                            // Send the detected file path to the processing channel.
-                            if let Err(e) = tx.send(path).await {
-                                log_error("Failed to send file path to processor", &e);
+
+                            // Hand the detected file path to the job queue; never blocks.
+                            if queue.push(path) {
+                                metrics().record_detected(DetectionSource::Watcher, 1);
                             }
                         }
                     }
@@ -64,4 +68,152 @@ pub async fn watch_files(path: &Path, tx: mpsc::Sender<PathBuf>, config: &Watche
         }
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Create a watcher for `path` using the configured backend.
+/// If the native backend cannot be started and fallback is enabled, a polling
+/// watcher is used instead.
+fn start_watcher(
+    path: &Path,
+    tx_notify: mpsc::Sender<NotifyResult>,
+    queue: Arc<JobQueue>,
+    config: &WatcherConfig
+) -> Result<Box<dyn notify::Watcher + Send>, AppError> {
+    match config.backend {
+        WatcherBackend::Poll => start_backend_watcher(path, tx_notify, queue, config, WatcherBackend::Poll),
+        WatcherBackend::Native => match start_backend_watcher(path, tx_notify.clone(), Arc::clone(&queue), config, WatcherBackend::Native) {
+            Ok(watcher) => Ok(watcher),
+            Err(e) if config.fallback_to_poll => {
+                log_warning("Native watcher failed to initialize, falling back to polling", &format!("{}: {}", path.display(), e));
+                start_backend_watcher(path, tx_notify, queue, config, WatcherBackend::Poll)
+            }
+            Err(e) => Err(e),
+        },
+    }
+}
+
+fn start_backend_watcher(
+    path: &Path,
+    tx_notify: mpsc::Sender<NotifyResult>,
+    queue: Arc<JobQueue>,
+    config: &WatcherConfig,
+    backend: WatcherBackend
+) -> Result<Box<dyn notify::Watcher + Send>, AppError> {
+    use notify::{recommended_watcher, PollWatcher, Watcher};
+
+    // Never block the notify thread: when the event channel is full the event is
+    // dropped and the queue schedules a rescan to recover it.
+    let handler = move |res: NotifyResult| {
+        match tx_notify.try_send(res) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => queue.record_dropped(),
+            Err(TrySendError::Closed(_)) => log_debug("Dropping notify event", "watcher is shutting down"),
+        }
+    };
+
+    let mut watcher: Box<dyn Watcher + Send> = match backend {
+        WatcherBackend::Native => Box::new(recommended_watcher(handler).map_err(|e| {
+            log_error("Failed to create file watcher", &e);
+            AppError::Watch(e)
+        })?),
+        WatcherBackend::Poll => {
+            let notify_config = notify::Config::default()
+                .with_poll_interval(Duration::from_millis(config.poll_interval_ms));
+            Box::new(PollWatcher::new(handler, notify_config).map_err(|e| {
+                log_error("Failed to create polling file watcher", &e);
+                AppError::Watch(e)
+            })?)
+        }
+    };
+
+    let recursive_mode = if config.recursive {
+        notify::RecursiveMode::Recursive
+    } else {
+        notify::RecursiveMode::NonRecursive
+    };
+
+    watcher.watch(path, recursive_mode).map_err(|e| {
+        log_error("Failed to watch directory", &format!("{}: {}", path.display(), e));
+        AppError::Watch(e)
+    })?;
+
+    log_info("Watcher backend started", &format!("{:?} for {}", backend, path.display()));
+    Ok(watcher)
+}
+
+/// Keep a watcher running on `path`, re-creating it with exponential backoff
+/// whenever it fails or reports that events were missed.
+/// After every failure the directory is rescanned so nothing dropped in the
+/// meantime is lost. Returns once the job queue has been closed.
+pub async fn supervise_watcher(path: &Path, queue: Arc<JobQueue>, config: &WatcherConfig) {
+    let max_delay = Duration::from_secs(config.restart_max_delay_sec);
+    let mut delay = Duration::from_millis(config.restart_initial_delay_ms);
+
+    while !queue.is_closed() {
+        let started = Instant::now();
+        match watch_files(path, Arc::clone(&queue), config).await {
+            Ok(()) => {
+                log_info("File watcher stopped", &format!("{}", path.display()));
+                break;
+            }
+            Err(e) => {
+                log_error("File watcher experienced an error", &e);
+                metrics().record_watcher_error();
+                set_watcher_state(WatcherState::Restarting, Some(format!("{:#}", e)));
+            }
+        }
+
+        // A watcher that ran healthily for a while starts over with the shortest delay
+        if started.elapsed() > max_delay {
+            delay = Duration::from_millis(config.restart_initial_delay_ms);
+        }
+
+        if let Err(e) = enqueue_existing_files(path, &queue, config.recursive).await {
+            log_error("Rescan after watcher failure failed", &e);
+        }
+
+        log_info("Restarting file watcher", &format!("{} in {}ms", path.display(), delay.as_millis()));
+        sleep(delay).await;
+        delay = Duration::min(delay * 2, max_delay);
+    }
+    set_watcher_state(WatcherState::Stopped, None);
+}
+
+/// Periodically rescan the input directory and enqueue every file found, to catch
+/// files whose notify events were lost. Files already queued or being processed
+/// are de-duplicated.
+pub async fn rescan_periodically(path: &Path, queue: Arc<JobQueue>, config: &WatcherConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.rescan_interval_sec));
+    // The first tick completes immediately; the initial scan already covered it.
+    interval.tick().await;
+
+    log_info("Periodic rescan enabled", &format!("every {}s for {}", config.rescan_interval_sec, path.display()));
+
+    while !queue.is_closed() {
+        interval.tick().await;
+        if let Err(e) = enqueue_existing_files(path, &queue, config.recursive).await {
+            log_error("Periodic rescan failed", &e);
+        }
+    }
+}
+
+/// Rescan the input directory whenever the job queue overflowed and has drained again
+pub async fn rescan_on_spill(path: &Path, queue: Arc<JobQueue>, config: &WatcherConfig) {
+    while queue.wait_for_spill().await {
+        log_info("Rescanning input directory after queue overflow", &format!("{}", path.display()));
+        if let Err(e) = enqueue_existing_files(path, &queue, config.recursive).await {
+            log_error("Rescan after queue overflow failed", &e);
+        }
+    }
+}
+
+/// Scan the input directory once and queue every file found.
+/// Returns the number of files that were newly queued.
+pub async fn enqueue_existing_files(path: &Path, queue: &JobQueue, recursive: bool) -> Result<usize, AppError> {
+    let files = scan_input_directory(path, recursive).await?;
+    let found = files.len();
+    let queued = files.into_iter().map(|file| queue.push(file)).filter(|queued| *queued).count();
+    metrics().record_detected(DetectionSource::Scan, queued as u64);
+    log_debug("Scanned input directory", &format!("{} files found, {} queued from {}", found, queued, path.display()));
+    Ok(queued)
+}
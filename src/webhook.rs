@@ -0,0 +1,328 @@
+use crate::config::{WebhookConfig, WebhookEvent, WebhookTarget};
+use crate::error::AppError;
+use crate::job::{current_job, in_job, JobId};
+use crate::logging::{log_debug, log_error, log_warning};
+use crate::processor::ProcessingResult;
+use crate::utils::unix_millis;
+
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
+use tokio::sync::Notify;
+use tokio::time::{sleep, timeout, Duration};
+
+/// Header carrying `sha256=<hex HMAC-SHA256 of the body>` when the target has a secret
+pub const SIGNATURE_HEADER: &str = "X-CFP-Signature-256";
+/// Header carrying the event name, e.g. `completed`
+pub const EVENT_HEADER: &str = "X-CFP-Event";
+/// Header carrying an ID that stays the same across retries of one delivery
+pub const DELIVERY_HEADER: &str = "X-CFP-Delivery";
+
+/// Deliveries that have not finished yet, across all notifiers
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+static IDLE: Notify = Notify::const_new();
+
+/// Sends webhooks about finished jobs to the configured targets.
+/// Deliveries run in the background, so slow endpoints never hold up processing.
+pub struct WebhookNotifier {
+    config: WebhookConfig,
+    targets: Vec<(WebhookTarget, Vec<glob::Pattern>)>,
+    client: reqwest::Client,
+}
+
+impl WebhookNotifier {
+    /// Create a notifier, or `None` if no target is configured
+    pub fn new(config: &WebhookConfig) -> Result<Option<Self>, AppError> {
+        if config.targets.is_empty() {
+            return Ok(None);
+        }
+        let targets = config.targets.iter().map(|target| {
+            let patterns = target.files.iter()
+                .map(|pattern| glob::Pattern::new(pattern)
+                    .map_err(|e| AppError::Config { message: format!("Invalid webhook file pattern '{}'", pattern), source: Some(e.into()) }))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((target.clone(), patterns))
+        }).collect::<Result<Vec<_>, AppError>>()?;
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .user_agent(concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| AppError::Config { message: "Failed to create webhook client".to_string(), source: Some(e.into()) })?;
+        Ok(Some(Self { config: config.clone(), targets, client }))
+    }
+
+    /// Notify the targets that a file was processed
+    pub fn completed(&self, result: &ProcessingResult) {
+        let payload = json!({
+            "event": WebhookEvent::Completed,
+            "timestamp_ms": unix_millis(SystemTime::now()),
+            "job_id": result.job_id,
+            "file": result.input_file,
+            "output_file": result.output_file,
+            "original_size": result.original_size,
+            "processed_size": result.processed_size,
+            "processing_time_ms": result.processing_time_ms,
+            "strategy": result.strategy,
+            "strategy_info": result.strategy_info,
+        });
+        self.send(WebhookEvent::Completed, &result.input_file, payload);
+    }
+
+    /// Notify the targets that processing a file failed
+    pub fn failed(&self, job_id: JobId, file: &Path, strategy: &str, error: &AppError) {
+        let payload = json!({
+            "event": WebhookEvent::Failed,
+            "timestamp_ms": unix_millis(SystemTime::now()),
+            "job_id": job_id,
+            "file": file,
+            "strategy": strategy,
+            "error": format!("{:#}", error),
+            "error_code": error.code(),
+            "retryable": error.is_retryable(),
+        });
+        self.send(WebhookEvent::Failed, file, payload);
+    }
+
+    fn send(&self, event: WebhookEvent, file: &Path, payload: serde_json::Value) {
+        let file_name = file.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let body = payload.to_string();
+        for (target, patterns) in &self.targets {
+            if !target.events.contains(&event) {
+                continue;
+            }
+            if !patterns.is_empty() && !patterns.iter().any(|pattern| pattern.matches(&file_name)) {
+                continue;
+            }
+
+            let delivery = Delivery {
+                client: self.client.clone(),
+                target: target.clone(),
+                event,
+                id: uuid::Uuid::new_v4().to_string(),
+                body: body.clone(),
+                max_attempts: self.config.max_attempts,
+                retry_delay: Duration::from_millis(self.config.retry_delay_ms),
+                max_retry_delay: Duration::from_millis(self.config.max_retry_delay_ms),
+            };
+            PENDING.fetch_add(1, Ordering::SeqCst);
+            let job = current_job();
+            tokio::spawn(async move {
+                match job {
+                    Some(job) => in_job(job, delivery.run()).await,
+                    None => delivery.run().await,
+                }
+                if PENDING.fetch_sub(1, Ordering::SeqCst) == 1 {
+                    IDLE.notify_waiters();
+                }
+            });
+        }
+    }
+}
+
+/// Wait until every webhook delivery started so far has succeeded or been given
+/// up, but at most `limit`. Returns false if deliveries were still pending.
+pub async fn wait_for_deliveries(limit: Duration) -> bool {
+    let all_done = async {
+        loop {
+            let idle = IDLE.notified();
+            if PENDING.load(Ordering::SeqCst) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    };
+    if timeout(limit, all_done).await.is_ok() {
+        return true;
+    }
+    log_warning("Stopped waiting for webhook deliveries", &format!(
+        "{} still pending after {}s", PENDING.load(Ordering::SeqCst), limit.as_secs()
+    ));
+    false
+}
+
+/// `sha256=<hex>` signature of `body` with `secret`, as sent in `SIGNATURE_HEADER`
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC key of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Check a received `SIGNATURE_HEADER` value against `body`, in constant time
+pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(hex_signature) = signature.strip_prefix("sha256=") else {
+        return false;
+    };
+    let Ok(expected) = hex::decode(hex_signature) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC key of any length");
+    mac.update(body);
+    mac.verify_slice(&expected).is_ok()
+}
+
+/// One payload on its way to one target
+struct Delivery {
+    client: reqwest::Client,
+    target: WebhookTarget,
+    event: WebhookEvent,
+    id: String,
+    body: String,
+    max_attempts: u32,
+    retry_delay: Duration,
+    max_retry_delay: Duration,
+}
+
+impl Delivery {
+    /// Send the payload, retrying on connection errors, timeouts, 429 and 5xx responses
+    async fn run(self) {
+        let mut delay = self.retry_delay.min(self.max_retry_delay);
+        for attempt in 1..=self.max_attempts {
+            let outcome = match self.attempt().await {
+                Ok(status) if status.is_success() => {
+                    log_debug("Webhook delivered", &format!("{} {} to {} ({})", self.event.as_str(), self.id, self.target.url, status));
+                    return;
+                }
+                Ok(status) if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS => status.to_string(),
+                Ok(status) => {
+                    log_error("Webhook rejected, not retrying", &format!("{} {} to {}: {}", self.event.as_str(), self.id, self.target.url, status));
+                    return;
+                }
+                Err(e) => e.to_string(),
+            };
+
+            if attempt == self.max_attempts {
+                log_error("Webhook delivery failed", &format!(
+                    "{} {} to {} after {} attempts: {}",
+                    self.event.as_str(), self.id, self.target.url, attempt, outcome
+                ));
+                return;
+            }
+            log_warning("Webhook delivery failed, retrying", &format!(
+                "{} to {} (attempt {}/{}): {}; next attempt in {}ms",
+                self.id, self.target.url, attempt, self.max_attempts, outcome, delay.as_millis()
+            ));
+            sleep(delay).await;
+            delay = delay.saturating_mul(2).min(self.max_retry_delay);
+        }
+    }
+
+    async fn attempt(&self) -> Result<reqwest::StatusCode, reqwest::Error> {
+        let mut request = self.client.post(&self.target.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, self.event.as_str())
+            .header(DELIVERY_HEADER, &self.id);
+        if !self.target.secret.is_empty() {
+            request = request.header(SIGNATURE_HEADER, sign(&self.target.secret, self.body.as_bytes()));
+        }
+        Ok(request.body(self.body.clone()).send().await?.status())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{serve, Request, Response};
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+
+    /// Start a stand-in receiver that answers with `statuses` in turn, repeating the
+    /// last one. Returns its URL and the requests it received.
+    async fn receiver(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<Request>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&received);
+        tokio::spawn(serve(listener, move |request: Request| {
+            let log = Arc::clone(&log);
+            let statuses = statuses.clone();
+            async move {
+                let mut requests = log.lock().unwrap();
+                let status = statuses.get(requests.len()).or(statuses.last()).copied().unwrap_or(200);
+                requests.push(request);
+                Response::text(status, "")
+            }
+        }));
+        (url, received)
+    }
+
+    fn delivery(url: &str, secret: &str, max_attempts: u32) -> Delivery {
+        Delivery {
+            client: reqwest::Client::new(),
+            target: WebhookTarget {
+                url: url.to_string(),
+                secret: secret.to_string(),
+                events: vec![WebhookEvent::Completed],
+                files: Vec::new(),
+            },
+            event: WebhookEvent::Completed,
+            id: "delivery-1".to_string(),
+            body: r#"{"event":"completed"}"#.to_string(),
+            max_attempts,
+            retry_delay: Duration::from_millis(1),
+            max_retry_delay: Duration::from_millis(5),
+        }
+    }
+
+    #[test]
+    fn sign_computes_hmac_sha256() {
+        assert_eq!(
+            sign("key", b"The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn verify_signature_only_accepts_the_matching_signature() {
+        let signature = sign("s3cret", b"body");
+        assert!(verify_signature("s3cret", b"body", &signature));
+        assert!(!verify_signature("s3cret", b"body!", &signature));
+        assert!(!verify_signature("other", b"body", &signature));
+        assert!(!verify_signature("s3cret", b"body", signature.trim_start_matches("sha256=")));
+        assert!(!verify_signature("s3cret", b"body", "sha256=not-hex"));
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_and_rate_limits_with_the_same_delivery_id() {
+        let (url, received) = receiver(vec![500, 429, 200]).await;
+        delivery(&url, "s3cret", 5).run().await;
+
+        let requests = received.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        for request in requests.iter() {
+            assert_eq!(request.header("x-cfp-delivery"), Some("delivery-1"));
+            assert_eq!(request.header("x-cfp-event"), Some("completed"));
+            let signature = request.header("x-cfp-signature-256").unwrap();
+            assert!(verify_signature("s3cret", &request.body, signature));
+        }
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_client_errors() {
+        let (url, received) = receiver(vec![400]).await;
+        delivery(&url, "", 5).run().await;
+
+        let requests = received.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].header("x-cfp-signature-256"), None);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let (url, received) = receiver(vec![503]).await;
+        delivery(&url, "", 3).run().await;
+        assert_eq!(received.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn waiting_for_deliveries_is_bounded() {
+        assert!(wait_for_deliveries(Duration::from_millis(10)).await);
+        PENDING.fetch_add(1, Ordering::SeqCst);
+        let finished = wait_for_deliveries(Duration::from_millis(10)).await;
+        PENDING.fetch_sub(1, Ordering::SeqCst);
+        assert!(!finished);
+    }
+}